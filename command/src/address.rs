@@ -0,0 +1,80 @@
+// Who an envelope is for, when several devices take commands from one host:
+// every device, one device by its id, or every device in a group. A device
+// has one id and can be in a few groups, and drops whatever is addressed to
+// someone else without a reply.
+//
+// On the wire the address is the first ADDRESS_LEN bytes of the envelope
+// body, a kind then a little endian u16 id.
+
+pub const ADDRESS_LEN: usize = 3;
+pub const MAX_GROUPS: usize = 8;
+
+const ALL: u8 = 0;
+const DEVICE: u8 = 1;
+const GROUP: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Address {
+    All,
+    Device(u16),
+    Group(u16),
+}
+
+impl Address {
+    pub fn to_bytes(self) -> [u8; ADDRESS_LEN] {
+        let (kind, id) = match self {
+            Address::All => (ALL, 0),
+            Address::Device(id) => (DEVICE, id),
+            Address::Group(id) => (GROUP, id),
+        };
+        let [low, high] = id.to_le_bytes();
+        [kind, low, high]
+    }
+
+    pub fn from_bytes([kind, low, high]: [u8; ADDRESS_LEN]) -> Option<Self> {
+        let id = u16::from_le_bytes([low, high]);
+        match kind {
+            ALL => Some(Address::All),
+            DEVICE => Some(Address::Device(id)),
+            GROUP => Some(Address::Group(id)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub device: u16,
+    pub groups: heapless::Vec<u16, MAX_GROUPS>,
+}
+
+impl Identity {
+    pub fn accepts(&self, address: Address) -> bool {
+        match address {
+            Address::All => true,
+            Address::Device(id) => id == self.device,
+            Address::Group(id) => self.groups.contains(&id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_addresses() {
+        let identity = Identity { device: 3, groups: heapless::Vec::from_slice(&[1, 7]).unwrap() };
+        for (address, accepted) in [
+            (Address::All, true),
+            (Address::Device(3), true),
+            (Address::Device(4), false),
+            (Address::Group(7), true),
+            (Address::Group(3), false),
+        ] {
+            assert_eq!(identity.accepts(address), accepted, "{:?}", address);
+            assert_eq!(Address::from_bytes(address.to_bytes()), Some(address));
+        }
+        assert_eq!(Address::from_bytes([9, 0, 0]), None);
+    }
+}
@@ -0,0 +1,78 @@
+// A fixed-capacity byte buffer that goes over the wire as a CBOR byte string,
+// rather than the array of integers serde would make of a heapless::Vec<u8>.
+// That is up to half the size for pixel data, and decodes without allocation.
+
+use core::fmt;
+use core::ops::Deref;
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bytes<const N: usize>(heapless::Vec<u8, N>);
+
+impl<const N: usize> Bytes<N> {
+    pub fn new() -> Self {
+        Self(heapless::Vec::new())
+    }
+
+    // Fails if there is no room for the whole slice
+    pub(crate) fn extend_from_slice(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        self.0.extend_from_slice(bytes)
+    }
+
+    pub fn push(&mut self, byte: u8) -> Result<(), u8> {
+        self.0.push(byte)
+    }
+}
+
+impl<const N: usize> TryFrom<&[u8]> for Bytes<N> {
+    type Error = ();
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        heapless::Vec::from_slice(bytes).map(Self)
+    }
+}
+
+impl<const N: usize> Deref for Bytes<N> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<const N: usize> Serialize for Bytes<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+struct BytesVisitor<const N: usize>;
+
+impl<'de, const N: usize> Visitor<'de> for BytesVisitor<N> {
+    type Value = Bytes<N>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "at most {} bytes", N)
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        Bytes::try_from(bytes).map_err(|_| E::invalid_length(bytes.len(), &self))
+    }
+
+    // Also accept an array of integers, as a plain heapless::Vec<u8> would send
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Bytes::new();
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte).map_err(|_| de::Error::invalid_length(N + 1, &self))?;
+        }
+        Ok(bytes)
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for Bytes<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
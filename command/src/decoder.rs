@@ -0,0 +1,352 @@
+// Turns a stream of bytes, as it arrives from a socket, into requests, or
+// into replies on the host. Each comes in an envelope, see envelope.rs, which may arrive split
+// across reads or several to a read. Anything that does not check out is
+// dropped and decoding carries on from the next magic. A decoder with an
+// identity skips envelopes addressed to other devices.
+//
+// Bytes go straight into the decoder's buffer, so reading and decoding needs
+// no other buffer and no allocation:
+//
+//     let n = socket.read(decoder.space()).await?;
+//     decoder.filled(n);
+//     while let Some(command) = decoder.decode() { ... }
+
+use core::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::address::{Address, Identity, ADDRESS_LEN};
+use crate::envelope::{crc32, CRC_LEN, HEADER_LEN, MAGIC, MAX_PAYLOAD_LEN, PROTOCOL_VERSION};
+use crate::{Command, Request};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    // Bytes dropped looking for the start of an envelope
+    Garbage(usize),
+    // An envelope that failed its CRC, or had an impossible length
+    Corrupt,
+    // An envelope from a peer speaking another protocol version, skipped whole.
+    // 0 is a peer from before envelopes, sending bare CBOR commands.
+    Version(u8),
+    // An envelope whose payload is not a message this side knows
+    Unknown,
+    // An envelope too big for the buffer
+    TooLarge(usize),
+}
+
+pub struct Decoder<const N: usize, T = Request> {
+    buf: [u8; N],
+    len: usize,
+    identity: Option<Identity>,
+    message: PhantomData<T>,
+}
+
+impl<const N: usize, T: DeserializeOwned> Default for Decoder<N, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, T: DeserializeOwned> Decoder<N, T> {
+    pub fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            identity: None,
+            message: PhantomData,
+        }
+    }
+
+    // Only decode envelopes addressed to this device, one of its groups or all
+    pub fn with_identity(self, identity: Identity) -> Self {
+        Self { identity: Some(identity), ..self }
+    }
+
+    // Bytes waiting to be decoded, a partial command
+    pub fn pending(&self) -> usize {
+        self.len
+    }
+
+    // Drop anything buffered, e.g. when the connection closes
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    // The free part of the buffer, to read into before calling filled
+    pub fn space(&mut self) -> &mut [u8] {
+        &mut self.buf[self.len..]
+    }
+
+    // Mark n bytes of space as read into
+    pub fn filled(&mut self, n: usize) {
+        self.len = (self.len + n).min(N);
+    }
+
+    // Copy in as many bytes as fit, returning how many did
+    pub fn push(&mut self, bytes: &[u8]) -> usize {
+        let n = bytes.len().min(N - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&bytes[..n]);
+        self.len += n;
+        n
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.buf.copy_within(n..self.len, 0);
+        self.len -= n;
+    }
+
+    // Where the next magic starts, or all but a last byte that could be the
+    // start of one
+    fn find_magic(&self) -> usize {
+        let bytes = &self.buf[..self.len];
+        bytes
+            .windows(MAGIC.len())
+            .position(|window| window == MAGIC)
+            .unwrap_or(if bytes.last() == Some(&MAGIC[0]) { self.len - 1 } else { self.len })
+    }
+
+    // Dropped bytes that start with a whole command are from a peer that
+    // predates envelopes, rather than line noise
+    fn dropped(bytes: &[u8]) -> DecodeError {
+        let mut de = minicbor_serde::Deserializer::new(bytes);
+        match Command::deserialize(&mut de) {
+            Ok(_) => DecodeError::Version(0),
+            Err(_) => DecodeError::Garbage(bytes.len()),
+        }
+    }
+
+    // The message in an intact envelope body, or None if it is for another device
+    fn message(&self, body: &[u8]) -> Option<Result<T, DecodeError>> {
+        let Some((address, payload)) = body.split_first_chunk::<ADDRESS_LEN>() else {
+            return Some(Err(DecodeError::Unknown));
+        };
+        let Some(address) = Address::from_bytes(*address) else {
+            return Some(Err(DecodeError::Unknown));
+        };
+        if self.identity.as_ref().is_some_and(|identity| !identity.accepts(address)) {
+            return None;
+        }
+        let mut de = minicbor_serde::Deserializer::new(payload);
+        Some(match T::deserialize(&mut de) {
+            Ok(message) if de.decoder().position() == payload.len() => Ok(message),
+            _ => Err(DecodeError::Unknown),
+        })
+    }
+
+    // The next message, an error for anything dropped on the way to it, or
+    // None until more bytes arrive
+    pub fn decode(&mut self) -> Option<Result<T, DecodeError>> {
+        loop {
+            let garbage = self.find_magic();
+            if garbage > 0 {
+                let error = Self::dropped(&self.buf[..garbage]);
+                self.consume(garbage);
+                return Some(Err(error));
+            }
+            if self.len < HEADER_LEN {
+                return None;
+            }
+
+            let len = u16::from_le_bytes([self.buf[3], self.buf[4]]) as usize;
+            let end = HEADER_LEN + len;
+            let total = end + CRC_LEN;
+            if len > MAX_PAYLOAD_LEN {
+                // Skip the magic so the next one is found
+                self.consume(1);
+                return Some(Err(DecodeError::Corrupt));
+            }
+            if total > N {
+                self.consume(1);
+                return Some(Err(DecodeError::TooLarge(total)));
+            }
+            if self.len < total {
+                return None;
+            }
+
+            let crc = u32::from_le_bytes([self.buf[end], self.buf[end + 1], self.buf[end + 2], self.buf[end + 3]]);
+            if crc != crc32(&self.buf[2..end]) {
+                self.consume(1);
+                return Some(Err(DecodeError::Corrupt));
+            }
+
+            let version = self.buf[2];
+            let result = if version != PROTOCOL_VERSION {
+                Some(Err(DecodeError::Version(version)))
+            } else {
+                self.message(&self.buf[HEADER_LEN..end])
+            };
+            self.consume(total);
+            if result.is_some() {
+                return result;
+            }
+            // For another device, carry on with the next envelope
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::envelope::{encode, encode_to};
+    use crate::{Animation, Capabilities, Command, ErrorCode, Layout, ParamKind, Reply, Response};
+
+    fn commands() -> [Command; 3] {
+        [Command::Clear(1, 2, 3), Command::Animate(Animation::Snow), Command::Flush]
+    }
+
+    fn envelope<T: serde::Serialize>(message: &T) -> Vec<u8> {
+        encode(message, &mut [0; 256]).unwrap().to_vec()
+    }
+
+    fn stream() -> Vec<u8> {
+        commands().iter().flat_map(envelope).collect()
+    }
+
+    fn drain<const N: usize, T: DeserializeOwned>(decoder: &mut Decoder<N, T>) -> Vec<Result<T, DecodeError>> {
+        core::iter::from_fn(|| decoder.decode()).collect()
+    }
+
+    #[test]
+    fn test_whole_and_split_commands() {
+        let bytes = stream();
+
+        let mut decoder = Decoder::<128, Command>::new();
+        assert_eq!(decoder.push(&bytes), bytes.len());
+        assert_eq!(drain(&mut decoder), commands().map(Ok));
+
+        // One byte at a time, each command comes out once it is all there
+        let mut decoded = Vec::new();
+        for byte in &bytes {
+            let n = decoder.space().len().min(1);
+            decoder.space()[..n].copy_from_slice(&[*byte]);
+            decoder.filled(n);
+            decoded.extend(drain(&mut decoder));
+        }
+        assert_eq!(decoded, commands().map(Ok));
+        assert_eq!(decoder.pending(), 0);
+    }
+
+    #[test]
+    fn test_resync_after_corruption() {
+        let mut bytes = alloc::vec![1, 2, 3];
+        bytes.extend(stream());
+        // Flip a bit in the first command's payload
+        bytes[3 + HEADER_LEN] ^= 0x10;
+        // Bare CBOR from a peer that predates envelopes
+        bytes.extend(minicbor_serde::to_vec(Command::Flush).unwrap());
+        bytes.extend(envelope(&Command::TapTempo));
+
+        let mut decoder = Decoder::<256, Command>::new();
+        assert_eq!(decoder.push(&bytes), bytes.len());
+        let decoded = drain(&mut decoder);
+        assert_eq!(decoded[..2], [Err(DecodeError::Garbage(3)), Err(DecodeError::Corrupt)]);
+        // The rest of the corrupt envelope is dropped looking for the next magic
+        assert!(matches!(decoded[2], Err(DecodeError::Garbage(_))));
+        assert_eq!(decoded[3..5], [Ok(Command::Animate(Animation::Snow)), Ok(Command::Flush)]);
+        assert_eq!(decoded[5..], [Err(DecodeError::Version(0)), Ok(Command::TapTempo)]);
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        let mut bytes = envelope(&Command::Flush);
+        // A newer peer, with a CRC that matches
+        bytes[2] = PROTOCOL_VERSION + 1;
+        let end = bytes.len() - CRC_LEN;
+        let crc = crc32(&bytes[2..end]).to_le_bytes();
+        bytes[end..].copy_from_slice(&crc);
+        bytes.extend(envelope(&Command::Flush));
+
+        let mut decoder = Decoder::<64, Command>::new();
+        decoder.push(&bytes);
+        assert_eq!(drain(&mut decoder), [Err(DecodeError::Version(PROTOCOL_VERSION + 1)), Ok(Command::Flush)]);
+    }
+
+    #[test]
+    fn test_command_too_large() {
+        let text = Command::SetText(crate::Text::try_from("far too long for the buffer").unwrap());
+        let mut bytes = envelope(&text);
+        bytes.extend(envelope(&Command::Flush));
+
+        let mut decoder = Decoder::<32, Command>::new();
+        let mut decoded = Vec::new();
+        let mut sent = 0;
+        while sent < bytes.len() {
+            sent += decoder.push(&bytes[sent..]);
+            decoded.extend(drain(&mut decoder));
+        }
+        assert_eq!(decoded[0], Err(DecodeError::TooLarge(bytes.len() - envelope(&Command::Flush).len())));
+        assert_eq!(decoded.last(), Some(&Ok(Command::Flush)));
+    }
+
+    #[test]
+    fn test_addressing() {
+        let identity = Identity { device: 3, groups: heapless::Vec::from_slice(&[7]).unwrap() };
+        let addressed = |address: Address, command: &Command| encode_to(address, command, &mut [0; 64]).unwrap().to_vec();
+        let mut bytes = addressed(Address::Device(4), &Command::TapTempo);
+        bytes.extend(addressed(Address::Device(3), &Command::Flush));
+        bytes.extend(addressed(Address::Group(8), &Command::TapTempo));
+        bytes.extend(addressed(Address::Group(7), &Command::SyncBeat));
+        bytes.extend(addressed(Address::All, &Command::Query));
+
+        let mut decoder = Decoder::<256, Command>::new().with_identity(identity);
+        decoder.push(&bytes);
+        assert_eq!(drain(&mut decoder), [Ok(Command::Flush), Ok(Command::SyncBeat), Ok(Command::Query)]);
+        assert_eq!(decoder.pending(), 0);
+
+        // Without an identity everything is decoded
+        let mut decoder = Decoder::<256, Command>::new();
+        decoder.push(&bytes);
+        assert_eq!(drain(&mut decoder).len(), 5);
+    }
+
+    #[test]
+    fn test_replies() {
+        let capabilities = Capabilities {
+            width: 5,
+            height: 24,
+            layout: Layout::Columns,
+            animations: heapless::Vec::from_slice(&[Animation::Snow, Animation::Elementary(0)]).unwrap(),
+            params: heapless::Vec::from_slice(&[ParamKind::Speed]).unwrap(),
+            fps: 25,
+            protocol_version: PROTOCOL_VERSION,
+            firmware: crate::Text::try_from("rp2040 0.1.0").unwrap(),
+            device: 1,
+            groups: heapless::Vec::from_slice(&[2]).unwrap(),
+        };
+        let reply = Reply {
+            id: Some(7),
+            response: Response::Capabilities(capabilities.clone()),
+        };
+        let error = Reply {
+            id: None,
+            response: Response::Error(ErrorCode::BadFrame),
+        };
+        let mut bytes = envelope(&reply);
+        bytes.extend(envelope(&error));
+        // A request is not a reply
+        bytes.extend(envelope(&Request { id: Some(8), command: Command::Flush }));
+
+        let mut decoder = Decoder::<256, Reply>::new();
+        decoder.push(&bytes);
+        assert_eq!(drain(&mut decoder), [Ok(reply), Ok(error), Err(DecodeError::Unknown)]);
+
+        // The biggest capabilities still fit in a response
+        let largest = Reply {
+            id: Some(u32::MAX),
+            response: Response::Capabilities(Capabilities {
+                animations: core::iter::repeat_n(Animation::Elementary(255), crate::MAX_ANIMATIONS).collect(),
+                params: core::iter::repeat_n(ParamKind::FireSparking, crate::MAX_PARAM_KINDS).collect(),
+                firmware: core::iter::repeat_n('x', crate::MAX_TEXT_LEN).collect(),
+                device: u16::MAX,
+                groups: core::iter::repeat_n(u16::MAX, crate::MAX_GROUPS).collect(),
+                ..capabilities
+            }),
+        };
+        assert!(encode(&largest, &mut [0; crate::MAX_RESPONSE_LEN]).is_ok());
+    }
+}
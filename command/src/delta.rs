@@ -0,0 +1,296 @@
+// Delta frames for streaming animation, where most pixels stay the same from
+// one frame to the next. Every frame has a sequence number: keyframes carry
+// the whole frame as `Frame` strips, deltas carry only the pixels that changed
+// since the frame before. Both can be split into numbered parts when they do
+// not fit in one command.
+//
+// A delta is only applied on top of the frame it was made against. When
+// anything goes missing the device stops applying deltas until the next
+// keyframe, which the host sends every so often.
+//
+// Frames can carry a presentation time, in milliseconds on the host clock
+// the device is kept in step with by SyncClock. Without one a frame is shown
+// on the next Flush.
+//
+// Delta spans are a little endian u16 pixel index (row-major across the
+// display), a pixel count (1-255), then r, g, b for each pixel.
+
+use render_engine::fixedcolor::FixedColor;
+use render_engine::RenderBuffer;
+use serde::{Deserialize, Serialize};
+
+use crate::frame::{Frame, FrameBytes, FrameError, MAX_FRAME_BYTES};
+
+const SPAN_HEADER: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameHeader {
+    pub sequence: u32,
+    pub part: u16,
+    pub parts: u16,
+    pub present_at: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Delta {
+    // Width of the display the pixel indices run across
+    pub width: u16,
+    pub spans: FrameBytes,
+}
+
+impl Delta {
+    // The changed pixels from index `start` on, as many as fit in one delta,
+    // and the index to carry on from if they did not all fit
+    pub fn between(previous: &[(u8, u8, u8)], current: &[(u8, u8, u8)], width: u16, start: usize) -> Result<(Self, Option<usize>), FrameError> {
+        if previous.len() != current.len() {
+            return Err(FrameError::Truncated);
+        }
+        if current.len() > u16::MAX as usize + 1 {
+            return Err(FrameError::TooLarge);
+        }
+
+        let changed = |i: usize| previous[i] != current[i];
+        let mut spans = FrameBytes::new();
+        let mut i = start;
+        loop {
+            while i < current.len() && !changed(i) {
+                i += 1;
+            }
+            if i == current.len() {
+                return Ok((Self { width, spans }, None));
+            }
+            if spans.len() + SPAN_HEADER + 3 > MAX_FRAME_BYTES {
+                return Ok((Self { width, spans }, Some(i)));
+            }
+
+            let index = i;
+            let mut pixels = FrameBytes::new();
+            while i < current.len() && pixels.len() < u8::MAX as usize * 3 && spans.len() + SPAN_HEADER + pixels.len() + 3 <= MAX_FRAME_BYTES {
+                // Carry on over a single unchanged pixel, it costs the same as a new span
+                let bridge = i + 1 < current.len() && changed(i + 1) && pixels.len() < (u8::MAX as usize - 1) * 3;
+                if !changed(i) && !bridge {
+                    break;
+                }
+                let (r, g, b) = current[i];
+                let _ = pixels.extend_from_slice(&[r, g, b]);
+                i += 1;
+            }
+
+            let [low, high] = (index as u16).to_le_bytes();
+            let _ = spans.extend_from_slice(&[low, high, (pixels.len() / 3) as u8]);
+            let _ = spans.extend_from_slice(&pixels);
+        }
+    }
+
+    fn for_each(&self, mut put: impl FnMut(u16, u16, (u8, u8, u8))) -> Result<(), FrameError> {
+        let width = self.width.max(1) as usize;
+        let mut spans = &self.spans[..];
+        while !spans.is_empty() {
+            let [low, high, count, rest @ ..] = spans else {
+                return Err(FrameError::Truncated);
+            };
+            if *count == 0 {
+                return Err(FrameError::EmptyRun);
+            }
+            let index = u16::from_le_bytes([*low, *high]) as usize;
+            let len = *count as usize * 3;
+            let pixels = rest.get(..len).ok_or(FrameError::Truncated)?;
+            for (offset, pixel) in pixels.chunks_exact(3).enumerate() {
+                let i = index + offset;
+                put((i % width) as u16, (i / width) as u16, (pixel[0], pixel[1], pixel[2]));
+            }
+            spans = &rest[len..];
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), FrameError> {
+        self.for_each(|_, _, _| {})
+    }
+
+    pub fn write_to<const S: usize, const X: usize, const Y: usize>(&self, buffer: &mut RenderBuffer<S, X, Y>) -> Result<(), FrameError> {
+        self.validate()?;
+        self.for_each(|x, y, (r, g, b)| {
+            buffer.safe_set_pixel(x as u32, y as u32, FixedColor::from_rgb8(r, g, b));
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncError {
+    // Deltas are ignored until the next keyframe
+    WaitingForKeyframe,
+    // A frame or part went missing, so wait for the next keyframe
+    Missed,
+    Frame(FrameError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncState {
+    WaitingForKeyframe,
+    Receiving { sequence: u32, next_part: u16, keyframe: bool },
+    Complete(u32),
+}
+
+// Tracks keyframes and deltas on the device, applying each part only if
+// everything it depends on has arrived
+pub struct FrameSync {
+    state: SyncState,
+}
+
+impl Default for FrameSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameSync {
+    pub fn new() -> Self {
+        Self { state: SyncState::WaitingForKeyframe }
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.state != SyncState::WaitingForKeyframe
+    }
+
+    // The last frame received in full
+    pub fn sequence(&self) -> Option<u32> {
+        match self.state {
+            SyncState::Complete(sequence) => Some(sequence),
+            _ => None,
+        }
+    }
+
+    fn accepts(&self, header: &FrameHeader, keyframe: bool) -> Result<(), SyncError> {
+        let expected = match (self.state, header.part) {
+            // Any keyframe starts over
+            (_, 0) if keyframe => true,
+            (SyncState::WaitingForKeyframe, _) => return Err(SyncError::WaitingForKeyframe),
+            (SyncState::Complete(sequence), 0) => header.sequence == sequence.wrapping_add(1),
+            (SyncState::Receiving { sequence, next_part, keyframe: receiving }, part) => {
+                header.sequence == sequence && part == next_part && keyframe == receiving
+            }
+            _ => false,
+        };
+        if expected && header.part < header.parts {
+            Ok(())
+        } else {
+            Err(SyncError::Missed)
+        }
+    }
+
+    fn apply(&mut self, header: &FrameHeader, keyframe: bool, write: impl FnOnce() -> Result<(), FrameError>) -> Result<(), SyncError> {
+        let result = self.accepts(header, keyframe).and_then(|_| write().map_err(SyncError::Frame));
+        self.state = match result {
+            Ok(()) if header.part + 1 == header.parts => SyncState::Complete(header.sequence),
+            Ok(()) => SyncState::Receiving {
+                sequence: header.sequence,
+                next_part: header.part + 1,
+                keyframe,
+            },
+            Err(_) => SyncState::WaitingForKeyframe,
+        };
+        result
+    }
+
+    pub fn keyframe<const S: usize, const X: usize, const Y: usize>(&mut self, header: &FrameHeader, frame: &Frame, buffer: &mut RenderBuffer<S, X, Y>) -> Result<(), SyncError> {
+        self.apply(header, true, || frame.write_to(buffer))
+    }
+
+    pub fn delta<const S: usize, const X: usize, const Y: usize>(&mut self, header: &FrameHeader, delta: &Delta, buffer: &mut RenderBuffer<S, X, Y>) -> Result<(), SyncError> {
+        self.apply(header, false, || delta.write_to(buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::Encoding;
+
+    type Buffer = RenderBuffer<{ 4 * 3 }, 4, 3>;
+
+    const BLACK: (u8, u8, u8) = (0, 0, 0);
+    const RED: (u8, u8, u8) = (255, 0, 0);
+    const GREEN: (u8, u8, u8) = (0, 255, 0);
+
+    fn header(sequence: u32, part: u16, parts: u16) -> FrameHeader {
+        FrameHeader { sequence, part, parts, present_at: None }
+    }
+
+    fn pixels(buffer: &Buffer) -> [(u8, u8, u8); 12] {
+        core::array::from_fn(|i| buffer.get_pixel((i % 4) as u32, (i / 4) as u32).as_rgb8())
+    }
+
+    #[test]
+    fn test_delta_spans() {
+        let previous = [BLACK; 12];
+        let mut current = previous;
+        current[1] = RED;
+        current[3] = RED;
+        current[9] = GREEN;
+
+        let (delta, rest) = Delta::between(&previous, &current, 4, 0).unwrap();
+        assert_eq!(rest, None);
+        // One span over the unchanged pixel between 1 and 3, and one for 9
+        assert_eq!(delta.spans.len(), 3 + 9 + 3 + 3);
+
+        let mut buffer = Buffer::new();
+        delta.write_to(&mut buffer).unwrap();
+        assert_eq!(pixels(&buffer), current);
+
+        let (unchanged, _) = Delta::between(&current, &current, 4, 0).unwrap();
+        assert!(unchanged.spans.is_empty());
+    }
+
+    #[test]
+    fn test_large_delta_splits() {
+        let previous = [BLACK; 1000];
+        let current: [(u8, u8, u8); 1000] = core::array::from_fn(|i| (i as u8, 1, 2));
+        let mut frame = previous;
+        let mut start = 0;
+        let mut parts = 0;
+        loop {
+            let (delta, rest) = Delta::between(&previous, &current, 50, start).unwrap();
+            assert!(delta.spans.len() <= MAX_FRAME_BYTES);
+            delta
+                .for_each(|x, y, color| frame[y as usize * 50 + x as usize] = color)
+                .unwrap();
+            parts += 1;
+            match rest {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+        assert_eq!(parts, 2);
+        assert_eq!(frame, current);
+    }
+
+    #[test]
+    fn test_frame_sync() {
+        let mut sync = FrameSync::new();
+        let mut buffer = Buffer::new();
+        let keyframe = Frame::encode(0, 0, 4, 3, &[RED; 12], Encoding::Rle).unwrap();
+        let mut current = [RED; 12];
+        current[5] = GREEN;
+        let (delta, _) = Delta::between(&[RED; 12], &current, 4, 0).unwrap();
+
+        assert_eq!(sync.delta(&header(1, 0, 1), &delta, &mut buffer), Err(SyncError::WaitingForKeyframe));
+        sync.keyframe(&header(7, 0, 1), &keyframe, &mut buffer).unwrap();
+        assert_eq!(sync.sequence(), Some(7));
+        sync.delta(&header(8, 0, 1), &delta, &mut buffer).unwrap();
+        assert_eq!(pixels(&buffer), current);
+
+        // Frame 9 went missing
+        assert_eq!(sync.delta(&header(10, 0, 1), &delta, &mut buffer), Err(SyncError::Missed));
+        assert!(!sync.is_synced());
+        assert_eq!(sync.delta(&header(11, 0, 1), &delta, &mut buffer), Err(SyncError::WaitingForKeyframe));
+
+        // A keyframe in two parts, missing the second loses sync again
+        sync.keyframe(&header(12, 0, 2), &keyframe, &mut buffer).unwrap();
+        assert_eq!(sync.sequence(), None);
+        assert_eq!(sync.delta(&header(13, 0, 1), &delta, &mut buffer), Err(SyncError::Missed));
+        sync.keyframe(&header(14, 0, 2), &keyframe, &mut buffer).unwrap();
+        sync.keyframe(&header(14, 1, 2), &keyframe, &mut buffer).unwrap();
+        assert_eq!(sync.sequence(), Some(14));
+    }
+}
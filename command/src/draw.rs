@@ -0,0 +1,239 @@
+// Shapes and text the device draws itself, so a static scene takes a handful
+// of commands rather than one SetPixel per LED. Coordinates are signed so a
+// shape can hang off the edge of the display, anything outside is clipped.
+
+use render_engine::fixedcolor::FixedColor;
+use render_engine::font::{draw_text, TextColor, TextStyle};
+use render_engine::RenderBuffer;
+use serde::{Deserialize, Serialize};
+
+use crate::Command;
+
+pub type Rgb = (u8, u8, u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: i16,
+    pub y: i16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    // The part of the rectangle on the buffer, as x and y ranges
+    fn clip(&self, size_x: u32, size_y: u32) -> (core::ops::Range<i32>, core::ops::Range<i32>) {
+        let clip = |start: i16, len: u16, size: u32| {
+            let start = start as i32;
+            start.max(0)..(start + len as i32).min(size as i32)
+        };
+        (clip(self.x, self.width, size_x), clip(self.y, self.height, size_y))
+    }
+}
+
+fn color((r, g, b): Rgb) -> FixedColor {
+    FixedColor::from_rgb8(r, g, b)
+}
+
+// Evenly spaced from `from` at 0 to `to` at `steps - 1`
+fn lerp(from: Rgb, to: Rgb, step: i32, steps: i32) -> FixedColor {
+    if steps <= 1 {
+        return color(from);
+    }
+    let channel = |a: u8, b: u8| (a as i32 + (b as i32 - a as i32) * step / (steps - 1)) as u8;
+    color((channel(from.0, to.0), channel(from.1, to.1), channel(from.2, to.2)))
+}
+
+pub fn fill_rect<const S: usize, const X: usize, const Y: usize>(buffer: &mut RenderBuffer<S, X, Y>, rect: &Rect, rgb: Rgb) {
+    let size = buffer.size();
+    let (xs, ys) = rect.clip(size.x, size.y);
+    for y in ys {
+        for x in xs.clone() {
+            buffer.safe_set_pixel(x as u32, y as u32, color(rgb));
+        }
+    }
+}
+
+// Left to right when horizontal, top to bottom when not
+pub fn gradient<const S: usize, const X: usize, const Y: usize>(buffer: &mut RenderBuffer<S, X, Y>, rect: &Rect, from: Rgb, to: Rgb, horizontal: bool) {
+    let size = buffer.size();
+    let (xs, ys) = rect.clip(size.x, size.y);
+    for y in ys {
+        for x in xs.clone() {
+            let c = if horizontal {
+                lerp(from, to, x - rect.x as i32, rect.width as i32)
+            } else {
+                lerp(from, to, y - rect.y as i32, rect.height as i32)
+            };
+            buffer.safe_set_pixel(x as u32, y as u32, c);
+        }
+    }
+}
+
+// Bresenham, both ends included
+pub fn line<const S: usize, const X: usize, const Y: usize>(buffer: &mut RenderBuffer<S, X, Y>, from: Point, to: Point, rgb: Rgb) {
+    let (mut x, mut y) = (from.x as i32, from.y as i32);
+    let (x1, y1) = (to.x as i32, to.y as i32);
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    loop {
+        buffer.safe_set_pixel_signed(x, y, color(rgb));
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * error;
+        if e2 >= dy {
+            error += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            error += dx;
+            y += sy;
+        }
+    }
+}
+
+pub fn circle<const S: usize, const X: usize, const Y: usize>(buffer: &mut RenderBuffer<S, X, Y>, centre: Point, radius: u16, rgb: Rgb, filled: bool) {
+    let r = radius as i32;
+    // Half a pixel either side of the radius, so small circles look round
+    let r2 = r as i64 * r as i64;
+    let (outer, inner) = (r2 + r as i64, r2 - r as i64);
+    let bounds = Rect {
+        x: (centre.x as i32 - r).clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+        y: (centre.y as i32 - r).clamp(i16::MIN as i32, i16::MAX as i32) as i16,
+        width: (2 * r + 1).min(u16::MAX as i32) as u16,
+        height: (2 * r + 1).min(u16::MAX as i32) as u16,
+    };
+    let size = buffer.size();
+    let (xs, ys) = bounds.clip(size.x, size.y);
+    for y in ys {
+        for x in xs.clone() {
+            let (dx, dy) = ((x - centre.x as i32) as i64, (y - centre.y as i32) as i64);
+            let d2 = dx * dx + dy * dy;
+            if d2 <= outer && (filled || d2 > inner || r == 0) {
+                buffer.safe_set_pixel(x as u32, y as u32, color(rgb));
+            }
+        }
+    }
+}
+
+// In the built in 5 pixel high font, `scale` times as big
+pub fn text<const S: usize, const X: usize, const Y: usize>(buffer: &mut RenderBuffer<S, X, Y>, at: Point, rgb: Rgb, scale: u8, text: &str) {
+    let style = TextStyle::new(TextColor::Solid(color(rgb))).with_scale(scale as u32);
+    draw_text(buffer, text, at.x as i32, at.y as i32, &style);
+}
+
+impl Command {
+    // Draw a FillRect, Line, gradient, Circle or Text into the buffer. Returns
+    // false, leaving the buffer alone, for any other command.
+    pub fn draw_to<const S: usize, const X: usize, const Y: usize>(&self, buffer: &mut RenderBuffer<S, X, Y>) -> bool {
+        match self {
+            Command::FillRect(rect, rgb) => fill_rect(buffer, rect, *rgb),
+            Command::Line(from, to, rgb) => line(buffer, *from, *to, *rgb),
+            Command::HorizontalGradient(rect, from, to) => gradient(buffer, rect, *from, *to, true),
+            Command::VerticalGradient(rect, from, to) => gradient(buffer, rect, *from, *to, false),
+            Command::Circle(centre, radius, rgb, filled) => circle(buffer, *centre, *radius, *rgb, *filled),
+            Command::Text(at, rgb, scale, s) => text(buffer, *at, *rgb, *scale, s),
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Buffer = RenderBuffer<{ 8 * 6 }, 8, 6>;
+
+    const RED: Rgb = (255, 0, 0);
+
+    fn lit(buffer: &Buffer) -> usize {
+        buffer.buffer().iter().filter(|p| p.as_rgb8() != (0, 0, 0)).count()
+    }
+
+    #[test]
+    fn test_fill_rect_clips() {
+        let mut buffer = Buffer::new();
+        assert!(Command::FillRect(Rect { x: -2, y: 4, width: 4, height: 10 }, RED).draw_to(&mut buffer));
+        assert_eq!(lit(&buffer), 4);
+        assert_eq!(buffer.get_pixel(1, 5).as_rgb8(), RED);
+        assert_eq!(buffer.get_pixel(2, 5).as_rgb8(), (0, 0, 0));
+
+        assert!(!Command::Flush.draw_to(&mut buffer));
+    }
+
+    #[test]
+    fn test_gradients() {
+        let mut buffer = Buffer::new();
+        let rect = Rect { x: 0, y: 0, width: 3, height: 2 };
+        Command::HorizontalGradient(rect, (0, 0, 0), (200, 100, 0)).draw_to(&mut buffer);
+        assert_eq!(buffer.get_pixel(1, 1).as_rgb8(), (100, 50, 0));
+        assert_eq!(buffer.get_pixel(2, 0).as_rgb8(), (200, 100, 0));
+
+        Command::VerticalGradient(rect, (0, 0, 0), (0, 0, 255)).draw_to(&mut buffer);
+        assert_eq!(buffer.get_pixel(2, 0).as_rgb8(), (0, 0, 0));
+        assert_eq!(buffer.get_pixel(2, 1).as_rgb8(), (0, 0, 255));
+    }
+
+    #[test]
+    fn test_lines() {
+        let mut buffer = Buffer::new();
+        Command::Line(Point { x: 0, y: 0 }, Point { x: 5, y: 5 }, RED).draw_to(&mut buffer);
+        assert_eq!(lit(&buffer), 6);
+        assert!((0..6).all(|i| buffer.get_pixel(i, i).as_rgb8() == RED));
+
+        // Off the edge is clipped, not wrapped
+        let mut buffer = Buffer::new();
+        Command::Line(Point { x: -100, y: 2 }, Point { x: 100, y: 2 }, RED).draw_to(&mut buffer);
+        assert_eq!(lit(&buffer), 8);
+    }
+
+    #[test]
+    fn test_circles() {
+        let centre = Point { x: 3, y: 3 };
+        let mut buffer = Buffer::new();
+        Command::Circle(centre, 0, RED, false).draw_to(&mut buffer);
+        assert_eq!(lit(&buffer), 1);
+
+        let mut buffer = Buffer::new();
+        Command::Circle(centre, 2, RED, false).draw_to(&mut buffer);
+        assert_eq!(buffer.get_pixel(3, 3).as_rgb8(), (0, 0, 0));
+        assert_eq!(buffer.get_pixel(5, 3).as_rgb8(), RED);
+        let outline = lit(&buffer);
+
+        let mut buffer = Buffer::new();
+        Command::Circle(centre, 2, RED, true).draw_to(&mut buffer);
+        assert_eq!(buffer.get_pixel(3, 3).as_rgb8(), RED);
+        assert!(lit(&buffer) > outline);
+
+        // Too big to loop over, only what is on the buffer gets visited
+        let mut buffer = Buffer::new();
+        Command::Circle(centre, u16::MAX, RED, true).draw_to(&mut buffer);
+        assert_eq!(lit(&buffer), 8 * 6);
+    }
+
+    #[test]
+    fn test_text() {
+        let mut buffer = Buffer::new();
+        let text = crate::Text::try_from("L").unwrap();
+        Command::Text(Point { x: 1, y: 0 }, RED, 1, text).draw_to(&mut buffer);
+        assert_eq!(lit(&buffer), 7);
+        assert_eq!(buffer.get_pixel(1, 0).as_rgb8(), RED);
+
+        // The largest text at the largest scale only visits the pixels it can reach
+        let mut buffer = Buffer::new();
+        let text: crate::Text = core::iter::repeat_n('W', crate::MAX_TEXT_LEN).collect();
+        Command::Text(Point { x: -3, y: -3 }, RED, u8::MAX, text).draw_to(&mut buffer);
+        assert_eq!(lit(&buffer), 8 * 6);
+    }
+}
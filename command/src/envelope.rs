@@ -0,0 +1,99 @@
+// Every message goes over the wire in an envelope, so a corrupted or dropped
+// byte costs one command rather than the rest of the stream:
+//
+//     magic (2) | version (1) | length (2, LE) | address (3) | CBOR command | CRC-32 (4, LE)
+//
+// The length counts the address and command, see address.rs for the address.
+// The CRC covers the version, length, address and command. The layout of the envelope
+// stays the same from one protocol version to the next, so a peer can always
+// find the end of a command it does not understand and report the version.
+// The magic starts with a byte that is never valid CBOR, so a peer still
+// expecting bare CBOR rejects it rather than misreading it.
+
+use minicbor::encode::write::Cursor;
+use serde::Serialize;
+
+use crate::address::{Address, ADDRESS_LEN};
+
+pub const MAGIC: [u8; 2] = [0xfc, 0x4c];
+pub const PROTOCOL_VERSION: u8 = 1;
+pub const HEADER_LEN: usize = 5;
+pub const CRC_LEN: usize = 4;
+// Largest envelope, the device buffers a whole one before decoding it
+pub const MAX_ENVELOPE_LEN: usize = 4096;
+pub const MAX_PAYLOAD_LEN: usize = MAX_ENVELOPE_LEN - HEADER_LEN - CRC_LEN;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvelopeError {
+    // The command does not fit in the buffer, or in an envelope
+    TooLarge,
+}
+
+// CRC-32 as used by Ethernet and zip. Bitwise rather than a table, it is
+// little slower for a few KB and saves 1 KB of flash.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+// Write a command or response in its envelope for every device, returning
+// the bytes to send
+pub fn encode<'a, T: Serialize>(message: &T, out: &'a mut [u8]) -> Result<&'a [u8], EnvelopeError> {
+    encode_to(Address::All, message, out)
+}
+
+pub fn encode_to<'a, T: Serialize>(address: Address, message: &T, out: &'a mut [u8]) -> Result<&'a [u8], EnvelopeError> {
+    if out.len() < HEADER_LEN + ADDRESS_LEN + CRC_LEN {
+        return Err(EnvelopeError::TooLarge);
+    }
+    let payload_end = out.len() - CRC_LEN;
+    let mut serializer = minicbor_serde::Serializer::new(Cursor::new(&mut out[HEADER_LEN + ADDRESS_LEN..payload_end]));
+    message.serialize(&mut serializer).map_err(|_| EnvelopeError::TooLarge)?;
+    let len = ADDRESS_LEN + serializer.into_encoder().into_writer().position();
+    if len > MAX_PAYLOAD_LEN {
+        return Err(EnvelopeError::TooLarge);
+    }
+
+    out[..2].copy_from_slice(&MAGIC);
+    out[2] = PROTOCOL_VERSION;
+    out[3..HEADER_LEN].copy_from_slice(&(len as u16).to_le_bytes());
+    out[HEADER_LEN..HEADER_LEN + ADDRESS_LEN].copy_from_slice(&address.to_bytes());
+    let end = HEADER_LEN + len;
+    let crc = crc32(&out[2..end]);
+    out[end..end + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+    Ok(&out[..end + CRC_LEN])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Command;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_encode() {
+        let mut out = [0; 64];
+        let bytes = encode(&Command::Flush, &mut out).unwrap();
+        let payload = minicbor_serde::to_vec(Command::Flush).unwrap();
+        assert_eq!(bytes[..3], [0xfc, 0x4c, PROTOCOL_VERSION]);
+        assert_eq!(bytes[3..5], (ADDRESS_LEN as u16 + payload.len() as u16).to_le_bytes());
+        assert_eq!(bytes[5..8], [0, 0, 0]);
+        assert_eq!(bytes[8..8 + payload.len()], payload[..]);
+        assert_eq!(bytes.len(), HEADER_LEN + ADDRESS_LEN + payload.len() + CRC_LEN);
+
+        let bytes = encode_to(Address::Group(0x0102), &Command::Flush, &mut out).unwrap();
+        assert_eq!(bytes[5..8], [2, 0x02, 0x01]);
+
+        assert_eq!(encode(&Command::Flush, &mut [0; 10]), Err(EnvelopeError::TooLarge));
+    }
+}
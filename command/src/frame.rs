@@ -0,0 +1,261 @@
+// Whole frames, or strips of one, in a single command. Pixels are row-major
+// over a rectangle of the display, starting at its top left corner, and come
+// in one of three encodings:
+//
+// - Raw: r, g, b for every pixel
+// - Rle: runs of count (1-255), r, g, b
+// - Palette: up to 256 r, g, b colours, then a palette index per pixel
+//
+// Decoding writes straight into the device buffer with no allocation, after
+// checking the data is complete so a bad frame leaves the buffer untouched.
+
+use render_engine::fixedcolor::FixedColor;
+use render_engine::RenderBuffer;
+use serde::{Deserialize, Serialize};
+
+use crate::bytes::Bytes;
+
+// Most pixel data in one frame, 512 pixels raw. Bigger frames go as strips.
+pub const MAX_FRAME_BYTES: usize = 1536;
+pub const MAX_PALETTE: usize = 256;
+
+pub type FrameBytes = Bytes<MAX_FRAME_BYTES>;
+pub type Palette = Bytes<{ 3 * MAX_PALETTE }>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Raw,
+    Rle,
+    Palette,
+}
+
+// Sized for the largest encoding, there is no allocator on the device to box it
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FrameData {
+    Raw(FrameBytes),
+    Rle(FrameBytes),
+    Palette(Palette, FrameBytes),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    // The pixels do not fit in a frame with this encoding
+    TooLarge,
+    TooManyColors,
+    // Fewer pixels than the rectangle needs
+    Truncated,
+    // More pixels than the rectangle needs
+    TrailingData,
+    EmptyRun,
+    BadIndex,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Frame {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub data: FrameData,
+}
+
+impl Frame {
+    pub fn encode(x: u16, y: u16, width: u16, height: u16, pixels: &[(u8, u8, u8)], encoding: Encoding) -> Result<Self, FrameError> {
+        let len = width as usize * height as usize;
+        if pixels.len() < len {
+            return Err(FrameError::Truncated);
+        } else if pixels.len() > len {
+            return Err(FrameError::TrailingData);
+        }
+
+        let data = match encoding {
+            Encoding::Raw => {
+                let mut data = FrameBytes::new();
+                for &(r, g, b) in pixels {
+                    data.extend_from_slice(&[r, g, b]).map_err(|_| FrameError::TooLarge)?;
+                }
+                FrameData::Raw(data)
+            }
+            Encoding::Rle => {
+                let mut data = FrameBytes::new();
+                let mut pixels = pixels.iter().peekable();
+                while let Some(&color) = pixels.next() {
+                    let mut count = 1;
+                    while count < u8::MAX && pixels.next_if_eq(&&color).is_some() {
+                        count += 1;
+                    }
+                    let (r, g, b) = color;
+                    data.extend_from_slice(&[count, r, g, b]).map_err(|_| FrameError::TooLarge)?;
+                }
+                FrameData::Rle(data)
+            }
+            Encoding::Palette => {
+                let mut palette = Palette::new();
+                let mut indices = FrameBytes::new();
+                for &(r, g, b) in pixels {
+                    let index = match palette.chunks_exact(3).position(|c| c == [r, g, b]) {
+                        Some(index) => index,
+                        None => {
+                            palette.extend_from_slice(&[r, g, b]).map_err(|_| FrameError::TooManyColors)?;
+                            palette.len() / 3 - 1
+                        }
+                    };
+                    indices.push(index as u8).map_err(|_| FrameError::TooLarge)?;
+                }
+                FrameData::Palette(palette, indices)
+            }
+        };
+
+        Ok(Self { x, y, width, height, data })
+    }
+
+    // Bytes of pixel data, to pick the smallest encoding
+    pub fn data_len(&self) -> usize {
+        match &self.data {
+            FrameData::Raw(data) | FrameData::Rle(data) => data.len(),
+            FrameData::Palette(palette, indices) => palette.len() + indices.len(),
+        }
+    }
+
+    // Calls put with the position and colour of every pixel, in order
+    fn for_each(&self, mut put: impl FnMut(u16, u16, (u8, u8, u8))) -> Result<(), FrameError> {
+        let len = self.width as usize * self.height as usize;
+        let mut index = 0;
+        let mut next = |color| {
+            if index >= len {
+                return Err(FrameError::TrailingData);
+            }
+            let x = self.x.saturating_add((index % self.width as usize) as u16);
+            let y = self.y.saturating_add((index / self.width as usize) as u16);
+            put(x, y, color);
+            index += 1;
+            Ok(())
+        };
+
+        match &self.data {
+            FrameData::Raw(data) => {
+                let pixels = data.chunks_exact(3);
+                if !pixels.remainder().is_empty() {
+                    return Err(FrameError::Truncated);
+                }
+                for pixel in pixels {
+                    next((pixel[0], pixel[1], pixel[2]))?;
+                }
+            }
+            FrameData::Rle(data) => {
+                let runs = data.chunks_exact(4);
+                if !runs.remainder().is_empty() {
+                    return Err(FrameError::Truncated);
+                }
+                for run in runs {
+                    if run[0] == 0 {
+                        return Err(FrameError::EmptyRun);
+                    }
+                    for _ in 0..run[0] {
+                        next((run[1], run[2], run[3]))?;
+                    }
+                }
+            }
+            FrameData::Palette(palette, indices) => {
+                for &i in indices.iter() {
+                    let color = palette.get(i as usize * 3..i as usize * 3 + 3).ok_or(FrameError::BadIndex)?;
+                    next((color[0], color[1], color[2]))?;
+                }
+            }
+        }
+
+        if index < len {
+            return Err(FrameError::Truncated);
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), FrameError> {
+        self.for_each(|_, _, _| {})
+    }
+
+    // Pixels outside the buffer are clipped
+    pub fn write_to<const S: usize, const X: usize, const Y: usize>(&self, buffer: &mut RenderBuffer<S, X, Y>) -> Result<(), FrameError> {
+        self.validate()?;
+        self.for_each(|x, y, (r, g, b)| {
+            buffer.safe_set_pixel(x as u32, y as u32, FixedColor::from_rgb8(r, g, b));
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Buffer = RenderBuffer<{ 4 * 3 }, 4, 3>;
+
+    const RED: (u8, u8, u8) = (255, 0, 0);
+    const BLUE: (u8, u8, u8) = (0, 0, 255);
+
+    fn pixels() -> [(u8, u8, u8); 6] {
+        [RED, RED, RED, BLUE, BLUE, RED]
+    }
+
+    #[test]
+    fn test_encodings_round_trip() {
+        for encoding in [Encoding::Raw, Encoding::Rle, Encoding::Palette] {
+            let frame = Frame::encode(1, 1, 3, 2, &pixels(), encoding).unwrap();
+            let mut buffer = Buffer::new();
+            frame.write_to(&mut buffer).unwrap();
+
+            assert_eq!(buffer.get_pixel(1, 1).as_rgb8(), RED, "{:?}", encoding);
+            assert_eq!(buffer.get_pixel(2, 2).as_rgb8(), BLUE, "{:?}", encoding);
+            assert_eq!(buffer.get_pixel(3, 2).as_rgb8(), RED, "{:?}", encoding);
+            assert_eq!(buffer.get_pixel(0, 0).as_rgb8(), (0, 0, 0), "{:?}", encoding);
+        }
+
+        assert_eq!(Frame::encode(0, 0, 3, 2, &pixels(), Encoding::Raw).unwrap().data_len(), 18);
+        assert_eq!(Frame::encode(0, 0, 3, 2, &pixels(), Encoding::Rle).unwrap().data_len(), 12);
+        assert_eq!(Frame::encode(0, 0, 3, 2, &pixels(), Encoding::Palette).unwrap().data_len(), 12);
+    }
+
+    #[test]
+    fn test_encode_limits() {
+        assert_eq!(Frame::encode(0, 0, 4, 2, &pixels(), Encoding::Raw), Err(FrameError::Truncated));
+        let big = [RED; MAX_FRAME_BYTES];
+        assert_eq!(Frame::encode(0, 0, 64, 24, &big, Encoding::Raw), Err(FrameError::TooLarge));
+        // Long runs are split at 255
+        assert_eq!(Frame::encode(0, 0, 64, 24, &big, Encoding::Rle).unwrap().data_len(), 4 * 7);
+
+        let colors: [(u8, u8, u8); 300] = core::array::from_fn(|i| ((i / 256) as u8, i as u8, 0));
+        assert_eq!(Frame::encode(0, 0, 300, 1, &colors, Encoding::Palette), Err(FrameError::TooManyColors));
+    }
+
+    #[test]
+    fn test_bad_frames_leave_buffer_alone() {
+        let bad = [
+            FrameData::Raw(Bytes::try_from(&[1, 2, 3, 4][..]).unwrap()),
+            FrameData::Rle(Bytes::try_from(&[0, 1, 2, 3][..]).unwrap()),
+            FrameData::Rle(Bytes::try_from(&[3, 1, 2, 3][..]).unwrap()),
+            FrameData::Palette(Bytes::try_from(&[1, 2, 3][..]).unwrap(), Bytes::try_from(&[0, 1][..]).unwrap()),
+        ];
+        let errors = [FrameError::Truncated, FrameError::EmptyRun, FrameError::TrailingData, FrameError::BadIndex];
+
+        for (data, error) in bad.into_iter().zip(errors) {
+            let frame = Frame { x: 0, y: 0, width: 2, height: 1, data };
+            let mut buffer = Buffer::new();
+            assert_eq!(frame.write_to(&mut buffer), Err(error));
+            assert!(buffer.buffer().iter().all(|p| p.as_rgb8() == (0, 0, 0)));
+        }
+    }
+
+    #[test]
+    fn test_bytes_are_a_cbor_byte_string() {
+        let frame = Frame::encode(0, 0, 3, 2, &pixels(), Encoding::Raw).unwrap();
+        let cbor = minicbor_serde::to_vec(&frame).unwrap();
+        // A byte string header (major type 2, length 18) then the pixels as they are
+        let mut raw = [0x40 | 18; 19];
+        for (i, (r, g, b)) in pixels().into_iter().enumerate() {
+            raw[1 + i * 3..4 + i * 3].copy_from_slice(&[r, g, b]);
+        }
+        assert!(cbor.windows(19).any(|w| w == raw));
+        let decoded: Frame = minicbor_serde::from_slice(&cbor).unwrap();
+        assert_eq!(decoded, frame);
+    }
+}
@@ -0,0 +1,188 @@
+// Smooth playback of streamed frames. The host stamps each frame with the
+// time it should be shown, in milliseconds on its own clock, and every so
+// often tells the device what that clock reads with SyncClock. The device
+// keeps the offset between the two clocks and holds complete frames in a
+// small queue until they are due, so Wi-Fi jitter up to the latency the host
+// leaves is smoothed out. Frames that are already late are dropped, as are
+// frames overtaken by a later one before they could be shown.
+//
+// Delay on the way only ever makes the host clock look further behind, so
+// the smallest offset seen is the best guess. It is taken over the last two
+// windows of samples, to follow the clocks as they drift apart.
+
+use heapless::Deque;
+
+const WINDOW: u32 = 16;
+
+pub struct ClockSync {
+    best: Option<i64>,
+    current: Option<i64>,
+    samples: u32,
+}
+
+impl Default for ClockSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockSync {
+    pub const fn new() -> Self {
+        Self { best: None, current: None, samples: 0 }
+    }
+
+    // The host clock read `host` when the device clock read `device`
+    pub fn sample(&mut self, host: u32, device: u64) {
+        let offset = device as i64 - host as i64;
+        self.current = Some(self.current.map_or(offset, |current| current.min(offset)));
+        self.samples += 1;
+        if self.samples == WINDOW {
+            self.best = self.current.take();
+            self.samples = 0;
+        }
+    }
+
+    // Device clock minus host clock, once there has been a sample
+    pub fn offset(&self) -> Option<i64> {
+        match (self.best, self.current) {
+            (Some(best), Some(current)) => Some(best.min(current)),
+            (best, current) => best.or(current),
+        }
+    }
+
+    pub fn to_device(&self, host: u32) -> Option<u64> {
+        self.offset().map(|offset| (host as i64 + offset).max(0) as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueError {
+    // Due before now, so it was dropped
+    Late,
+    // No room until the frames ahead of it have been shown
+    Full,
+}
+
+// Frames waiting to be shown, in the order they are due. Times are on the
+// device clock, in milliseconds.
+pub struct FrameQueue<T, const N: usize> {
+    frames: Deque<(u64, T), N>,
+    dropped: u32,
+}
+
+impl<T, const N: usize> Default for FrameQueue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> FrameQueue<T, N> {
+    pub const fn new() -> Self {
+        Self { frames: Deque::new(), dropped: 0 }
+    }
+
+    pub fn push(&mut self, due: u64, frame: T, now: u64) -> Result<(), QueueError> {
+        if due < now {
+            self.dropped = self.dropped.wrapping_add(1);
+            return Err(QueueError::Late);
+        }
+        // Anything due later is left from a stream that has started over
+        while self.frames.back().is_some_and(|(queued, _)| *queued > due) {
+            self.frames.pop_back();
+            self.dropped = self.dropped.wrapping_add(1);
+        }
+        self.frames.push_back((due, frame)).map_err(|_| QueueError::Full)
+    }
+
+    pub fn next_due(&self) -> Option<u64> {
+        self.frames.front().map(|(due, _)| *due)
+    }
+
+    // The latest frame that is due, dropping any it overtook
+    pub fn pop_due(&mut self, now: u64) -> Option<T> {
+        let mut latest = None;
+        while self.next_due().is_some_and(|due| due <= now) {
+            if latest.is_some() {
+                self.dropped = self.dropped.wrapping_add(1);
+            }
+            latest = self.frames.pop_front().map(|(_, frame)| frame);
+        }
+        latest
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // Frames that were never shown, for as long as the queue has been running
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_takes_the_least_delayed_sample() {
+        let mut clock = ClockSync::new();
+        assert_eq!(clock.to_device(0), None);
+
+        // The device clock is 1000 ahead, samples arrive 5 to 30 late
+        for (host, delay) in [(0, 30), (40, 5), (80, 12)] {
+            clock.sample(host, host as u64 + 1000 + delay);
+        }
+        assert_eq!(clock.offset(), Some(1005));
+        assert_eq!(clock.to_device(100), Some(1105));
+
+        // The clocks drift 20 closer, which shows once the old window is gone
+        for host in 0..2 * WINDOW {
+            clock.sample(host, host as u64 + 985 + 5);
+        }
+        assert_eq!(clock.offset(), Some(990));
+    }
+
+    #[test]
+    fn test_queue_presents_on_time() {
+        let mut queue = FrameQueue::<char, 4>::new();
+        queue.push(100, 'a', 0).unwrap();
+        queue.push(140, 'b', 0).unwrap();
+        assert_eq!(queue.push(10, 'x', 20), Err(QueueError::Late));
+
+        assert_eq!(queue.next_due(), Some(100));
+        assert_eq!(queue.pop_due(99), None);
+        assert_eq!(queue.pop_due(100), Some('a'));
+        assert_eq!(queue.pop_due(120), None);
+        assert_eq!(queue.pop_due(140), Some('b'));
+        assert!(queue.is_empty());
+        assert_eq!(queue.dropped(), 1);
+    }
+
+    #[test]
+    fn test_queue_drops_overtaken_frames() {
+        let mut queue = FrameQueue::<char, 4>::new();
+        for (due, frame) in [(100, 'a'), (140, 'b'), (180, 'c'), (220, 'd')] {
+            queue.push(due, frame, 0).unwrap();
+        }
+        assert_eq!(queue.push(260, 'e', 0), Err(QueueError::Full));
+
+        // Woken late, only the newest due frame is shown
+        assert_eq!(queue.pop_due(190), Some('c'));
+        assert_eq!(queue.dropped(), 2);
+
+        // A stream that starts over replaces what was queued after it
+        queue.push(50, 'f', 0).unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.pop_due(200), Some('f'));
+        assert_eq!(queue.dropped(), 3);
+    }
+}
@@ -1,24 +1,158 @@
 #![no_std]
 
 use serde::{Serialize, Deserialize};
+
+mod address;
+mod bytes;
+mod decoder;
+mod delta;
+mod draw;
+mod envelope;
+mod frame;
+mod jitter;
+mod response;
+
+pub use address::{Address, Identity, ADDRESS_LEN, MAX_GROUPS};
+pub use bytes::Bytes;
+pub use decoder::{DecodeError, Decoder};
+pub use delta::{Delta, FrameHeader, FrameSync, SyncError};
+pub use draw::{Point, Rect, Rgb};
+pub use envelope::{crc32, encode, encode_to, EnvelopeError, MAX_ENVELOPE_LEN, MAX_PAYLOAD_LEN, PROTOCOL_VERSION};
+pub use response::{Capabilities, ErrorCode, Layout, Reply, Response, MAX_ANIMATIONS, MAX_PARAM_KINDS, MAX_RESPONSE_LEN};
+pub use jitter::{ClockSync, FrameQueue, QueueError};
+pub use frame::{Encoding, Frame, FrameData, FrameError, FrameBytes, Palette, MAX_FRAME_BYTES, MAX_PALETTE};
 //use render_engine::RenderBuffer;
 
 //pub type SizedRenderBuffer = RenderBuffer<120, 5, 24>;
 
+// The marquee holds the longest text the device shows
+pub const MAX_TEXT_LEN: usize = render_engine::MARQUEE_CAPACITY;
+pub type Text = heapless::String<MAX_TEXT_LEN>;
+
+// The same limit as the engine's, so audio frames move straight across
+pub use render_engine::MAX_BANDS;
+pub const MAX_PARAMS: usize = 4;
+
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Animation {
     None,
     Snow,
     Sparkle,
-    Rainbow
+    Rainbow,
+    Marquee,
+    Fire,
+    Fireworks,
+    Life,
+    Elementary(u8), // Wolfram rule number, e.g. 30, 90 or 110
+    Plasma,
+    Aurora,
+    Icicle,
+    Matrix,
+    Countdown,
+    Snake,
+    Pong,
+    VuMeter,
+    BeatFlash,
+    Waterfall,
+    Pulse,
 }
 
-#[derive(PartialEq, Serialize, Deserialize)]
+// A controller event for the games
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Input {
+    Up,
+    Down,
+    Left,
+    Right,
+    Button,
+}
+
+// How one animation gives way to the next
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TransitionStyle {
+    Cut,
+    Crossfade,
+    FadeThroughBlack,
+    Wipe,
+}
+
+// A setting that can change while an animation runs
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Param {
+    Speed(f32), // 1.0 as designed, 0.0 frozen
+    FireCooling(u8),
+    FireSparking(u8),
+}
+
+// A Param without its value, for the device to list the ones it takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ParamKind {
+    Speed,
+    FireCooling,
+    FireSparking,
+}
+
+impl Param {
+    pub fn kind(&self) -> ParamKind {
+        match self {
+            Param::Speed(_) => ParamKind::Speed,
+            Param::FireCooling(_) => ParamKind::FireCooling,
+            Param::FireSparking(_) => ParamKind::FireSparking,
+        }
+    }
+}
+
+// One frame of audio analysis from the host, energies are 0-255
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioFrame {
+    pub bands: heapless::Vec<u8, MAX_BANDS>, // Lowest frequency first
+    pub level: u8,
+    pub beat: bool,
+}
+
+// Frames make this large, but there is no allocator on the device to box them
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum Command {
     // Clear the display to a specific colour
     Clear(u8, u8, u8),
     Flush,
     Animate(Animation),
     SetPixel(u8, u8, u8, u8, u8), // x, y, r, g, b
-//    SetBuffer(RenderBuffer<S, X, Y>) // x, y, buffer
+    SetText(Text), // Text for the marquee to scroll
+    SetTime(u64), // Local seconds since 1970-01-01 00:00
+    SetCountdownTarget(u16, u8, u8), // year, month, day
+    ClearCountdownTarget, // Count down to the next Christmas again
+    Input(u8, Input), // player, event
+    Audio(AudioFrame),
+    SetBpm(f32), // 0 stops the beat clock
+    TapTempo,
+    SyncBeat, // Now is the start of a beat
+    SetFrame(Frame), // A whole frame, or a strip of one, drawn into the buffer
+    Keyframe(FrameHeader, Frame), // A part of a whole frame in a stream
+    Delta(FrameHeader, Delta), // Pixels changed since the previous frame in a stream
+    Query, // Ask the device for its Capabilities
+    AnimateWith {
+        animation: Animation,
+        transition_duration: f32, // seconds
+        style: TransitionStyle,
+        params: heapless::Vec<Param, MAX_PARAMS>, // Set before the transition starts
+    },
+    SetParam(Param),
+    SetBrightness(u8), // 255 is full brightness
+    // Drawn into the buffer like SetPixel, clipped to the display
+    FillRect(Rect, Rgb),
+    Line(Point, Point, Rgb), // from, to, both ends included
+    HorizontalGradient(Rect, Rgb, Rgb), // left, right
+    VerticalGradient(Rect, Rgb, Rgb), // top, bottom
+    Circle(Point, u16, Rgb, bool), // centre, radius, colour, filled
+    Text(Point, Rgb, u8, Text), // top left, colour, scale, text
+    SyncClock(u32), // What the host clock reads now, for presentation times
+}
+
+// A command as it goes over the wire, with an id when the host wants a reply
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+    pub id: Option<u32>,
+    pub command: Command,
 }
@@ -0,0 +1,86 @@
+// Messages from a device back to the host, in the same envelopes as commands.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Animation, ParamKind, Text, MAX_GROUPS};
+
+// Largest response envelope, the device encodes into a buffer this size
+pub const MAX_RESPONSE_LEN: usize = 1024;
+// More than the animations there are, so new ones fit without a change here
+pub const MAX_ANIMATIONS: usize = 32;
+pub const MAX_PARAM_KINDS: usize = 16;
+
+// The order the LEDs are wired in, from the top left of the display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Layout {
+    Rows,
+    Columns,
+    // Every other row or column runs backwards, as strips zig-zag across
+    SerpentineRows,
+    SerpentineColumns,
+}
+
+// What a device is and what it can do, the answer to a Query
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub width: u16,
+    pub height: u16,
+    pub layout: Layout,
+    // Elementary is listed with rule 0, every rule works
+    pub animations: heapless::Vec<Animation, MAX_ANIMATIONS>,
+    // The params SetParam and AnimateWith can change
+    pub params: heapless::Vec<ParamKind, MAX_PARAM_KINDS>,
+    // Frames rendered per second
+    pub fps: u16,
+    pub protocol_version: u8,
+    pub firmware: Text,
+    // What the device answers to besides Address::All
+    pub device: u16,
+    pub groups: heapless::Vec<u16, MAX_GROUPS>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorCode {
+    // Bytes on the connection that were not an intact envelope
+    Corrupt,
+    // A message bigger than the device can take
+    TooLarge,
+    // Frame data that does not decode, or does not fit its rectangle
+    BadFrame,
+    // A delta that was not applied, the device is waiting for a keyframe
+    OutOfSync,
+    // Coordinates or a value outside what the device has
+    OutOfRange,
+    // A frame whose presentation time had passed, it was dropped
+    Late,
+    // A frame with a presentation time before any SyncClock
+    NoClock,
+}
+
+// Capabilities make this large, but as with commands there is nothing to box them in
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Response {
+    Ok,
+    Capabilities(Capabilities),
+    Error(ErrorCode),
+    // The device could not take the command right now, it was dropped
+    Busy,
+    // A command or protocol version the device does not know
+    Unsupported,
+}
+
+impl Response {
+    pub fn is_ok(&self) -> bool {
+        !matches!(self, Response::Error(_) | Response::Busy | Response::Unsupported)
+    }
+}
+
+// Every request with an id gets a reply with the same id. Failures are
+// reported without one too, for commands sent without an id or bytes that
+// never made it to a command.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reply {
+    pub id: Option<u32>,
+    pub response: Response,
+}
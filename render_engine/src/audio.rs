@@ -0,0 +1,283 @@
+// Effects that react to music. The host does the analysis and sends compact
+// feature frames, so all the device sees is the energy in a handful of
+// frequency bands, the overall level and whether this frame is on a beat.
+//
+// Frames arrive whenever the host sends them, so the effects take the louder
+// of what they are showing and the latest frame, and let it fall away between
+// frames. If the host stops sending everything decays to black.
+
+use crate::fixedcolor::FixedColor;
+use crate::render::Render;
+use crate::renderbuffer::Blend;
+use crate::RenderBuffer;
+
+pub const MAX_BANDS: usize = 16;
+
+// Energies are 0-255, lowest frequency band first
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct AudioFeatures {
+    pub bands: heapless::Vec<u8, MAX_BANDS>,
+    pub level: u8,
+    pub beat: bool,
+}
+
+impl AudioFeatures {
+    // Band energy from 0.0 to 1.0, interpolated at a position from 0.0 (lowest
+    // band) to 1.0 (highest band)
+    pub fn band_at(&self, position: f32) -> f32 {
+        let Some(last) = self.bands.len().checked_sub(1) else {
+            return 0.0;
+        };
+        let position = position.clamp(0.0, 1.0) * last as f32;
+        let index = position as usize;
+        let next = (index + 1).min(last);
+        let fraction = position - index as f32;
+        (self.bands[index] as f32 * (1.0 - fraction) + self.bands[next] as f32 * fraction) / 255.0
+    }
+
+    pub fn level(&self) -> f32 {
+        self.level as f32 / 255.0
+    }
+}
+
+// How far a bar falls each step, as a fraction of the display
+const VU_FALL: f32 = 0.04;
+// Peak markers hold for a while before falling more slowly than the bars
+const VU_PEAK_HOLD: u32 = 15;
+const VU_PEAK_FALL: f32 = 0.01;
+const VU_PEAK_COLOR: FixedColor = FixedColor::WHITE;
+
+// A VU meter per drop, lowest frequencies on the left
+pub struct VuMeter<const X: usize, const Y: usize> {
+    levels: [f32; X],
+    peaks: [(f32, u32); X],
+}
+
+impl<const X: usize, const Y: usize> VuMeter<X, Y> {
+    pub fn new() -> Self {
+        Self {
+            levels: [0.0; X],
+            peaks: [(0.0, 0); X],
+        }
+    }
+
+    pub fn set_audio(&mut self, features: &AudioFeatures) {
+        for (x, (level, peak)) in self.levels.iter_mut().zip(self.peaks.iter_mut()).enumerate() {
+            let position = if X > 1 { x as f32 / (X - 1) as f32 } else { 0.0 };
+            *level = level.max(features.band_at(position));
+            if *level >= peak.0 {
+                *peak = (*level, VU_PEAK_HOLD);
+            }
+        }
+    }
+
+    // Green at the bottom, through yellow to red at the top
+    fn color(height: f32) -> FixedColor {
+        FixedColor::from_hsv((1.0 - height) / 3.0, 1.0, 1.0)
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for VuMeter<X, Y> {
+    fn step(&mut self) {
+        for (level, peak) in self.levels.iter_mut().zip(self.peaks.iter_mut()) {
+            *level = (*level - VU_FALL).max(0.0);
+            *peak = match *peak {
+                (height, 0) => ((height - VU_PEAK_FALL).max(0.0), 0),
+                (height, hold) => (height, hold - 1),
+            };
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        for (x, (level, peak)) in self.levels.iter().zip(self.peaks.iter()).enumerate() {
+            let lit = libm::roundf(level * Y as f32) as u32;
+            for height in 0..lit {
+                let color = Self::color(height as f32 / Y as f32);
+                buffer.safe_set_pixel(x as u32, Y as u32 - 1 - height, color);
+            }
+
+            let peak = libm::roundf(peak.0 * Y as f32) as u32;
+            if peak > 0 {
+                buffer.safe_set_pixel(x as u32, Y as u32 - peak, VU_PEAK_COLOR);
+            }
+        }
+    }
+}
+
+// How much of the flash is left after each step
+const FLASH_DECAY: f32 = 0.85;
+// Turns around the colour wheel between flashes
+const FLASH_HUE_STEP: f32 = 0.17;
+// Brightness of the glow between beats at full level
+const FLASH_GLOW: f32 = 0.2;
+
+// Flashes the whole display on every beat, in a new colour each time
+pub struct BeatFlash {
+    flash: f32,
+    glow: f32,
+    hue: f32,
+}
+
+impl BeatFlash {
+    pub fn new() -> Self {
+        Self {
+            flash: 0.0,
+            glow: 0.0,
+            hue: 0.0,
+        }
+    }
+
+    pub fn set_audio(&mut self, features: &AudioFeatures) {
+        if features.beat {
+            self.flash = 1.0;
+            self.hue = (self.hue + FLASH_HUE_STEP) % 1.0;
+        }
+        self.glow = self.glow.max(features.level());
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for BeatFlash {
+    fn step(&mut self) {
+        self.flash *= FLASH_DECAY;
+        self.glow *= FLASH_DECAY;
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        let brightness = self.flash.max(self.glow * FLASH_GLOW);
+        buffer.clear_to_color(FixedColor::from_hsv(self.hue, 1.0, brightness));
+    }
+}
+
+// Steps between each new row of the waterfall
+const WATERFALL_STEPS_PER_ROW: u32 = 2;
+const WATERFALL_FALL: f32 = 0.1;
+
+// A spectrogram that scrolls down the drops, the newest row at the top
+pub struct Waterfall<const X: usize, const Y: usize> {
+    rows: [[u8; X]; Y],
+    // The newest row, that the latest frames are merged into
+    current: [f32; X],
+    // Index of the newest row in `rows`
+    top: usize,
+    steps: u32,
+}
+
+impl<const X: usize, const Y: usize> Waterfall<X, Y> {
+    pub fn new() -> Self {
+        Self {
+            rows: [[0; X]; Y],
+            current: [0.0; X],
+            top: 0,
+            steps: 0,
+        }
+    }
+
+    pub fn set_audio(&mut self, features: &AudioFeatures) {
+        for (x, energy) in self.current.iter_mut().enumerate() {
+            let position = if X > 1 { x as f32 / (X - 1) as f32 } else { 0.0 };
+            *energy = energy.max(features.band_at(position));
+        }
+    }
+
+    // Dark blue through purple and red to yellow
+    fn color(energy: u8) -> FixedColor {
+        let energy = energy as f32 / 255.0;
+        FixedColor::from_hsv(0.66 + energy * 0.5, 1.0 - energy * 0.3, energy)
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Waterfall<X, Y> {
+    fn step(&mut self) {
+        self.steps += 1;
+        if self.steps >= WATERFALL_STEPS_PER_ROW && Y > 0 {
+            self.steps = 0;
+            self.top = (self.top + Y - 1) % Y;
+            for (cell, energy) in self.rows[self.top].iter_mut().zip(self.current.iter_mut()) {
+                *cell = (*energy * 255.0) as u8;
+                *energy = (*energy - WATERFALL_FALL).max(0.0);
+            }
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        for y in 0..Y {
+            let row = &self.rows[(self.top + y) % Y];
+            for (x, &energy) in row.iter().enumerate() {
+                buffer.safe_set_pixel(x as u32, y as u32, Self::color(energy));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Buffer = RenderBuffer<{ 4 * 10 }, 4, 10>;
+
+    fn features(bands: &[u8], level: u8, beat: bool) -> AudioFeatures {
+        AudioFeatures {
+            bands: heapless::Vec::from_slice(bands).unwrap(),
+            level,
+            beat,
+        }
+    }
+
+    #[test]
+    fn test_band_at() {
+        let audio = features(&[0, 255, 51], 0, false);
+        assert_eq!(audio.band_at(0.0), 0.0);
+        assert_eq!(audio.band_at(0.25), 0.5);
+        assert_eq!(audio.band_at(1.0), 0.2);
+        assert_eq!(AudioFeatures::default().band_at(0.5), 0.0);
+    }
+
+    #[test]
+    fn test_vu_meter_falls() {
+        let mut vu = VuMeter::<4, 10>::new();
+        vu.set_audio(&features(&[255, 0, 0, 127], 0, false));
+        let mut buffer = Buffer::new();
+        vu.render(0.0, 0.0, &mut buffer, Blend::Dest);
+        assert_ne!(buffer.get_pixel(0, 0).as_rgb8(), (0, 0, 0));
+        assert_eq!(buffer.get_pixel(1, 9).as_rgb8(), (0, 0, 0));
+        assert_eq!(buffer.get_pixel(3, 4).as_rgb8(), (0, 0, 0));
+        assert_ne!(buffer.get_pixel(3, 5).as_rgb8(), (0, 0, 0));
+
+        for _ in 0..200 {
+            <VuMeter<4, 10> as Render<{ 4 * 10 }, 4, 10>>::step(&mut vu);
+        }
+        assert_eq!(vu.levels, [0.0; 4]);
+        assert_eq!(vu.peaks.map(|(height, _)| height), [0.0; 4]);
+    }
+
+    #[test]
+    fn test_beat_flash() {
+        let mut flash = BeatFlash::new();
+        flash.set_audio(&features(&[], 0, true));
+        let mut buffer = Buffer::new();
+        flash.render(0.0, 0.0, &mut buffer, Blend::Dest);
+        let bright = buffer.get_pixel(2, 2).as_rgb8();
+
+        <BeatFlash as Render<{ 4 * 10 }, 4, 10>>::step(&mut flash);
+        flash.set_audio(&features(&[], 0, false));
+        flash.render(0.0, 0.0, &mut buffer, Blend::Dest);
+        assert!(buffer.get_pixel(2, 2).as_rgb8().0 < bright.0);
+    }
+
+    #[test]
+    fn test_waterfall_scrolls() {
+        let mut waterfall = Waterfall::<4, 10>::new();
+        waterfall.set_audio(&features(&[255, 0], 0, false));
+        for _ in 0..WATERFALL_STEPS_PER_ROW * 3 {
+            <Waterfall<4, 10> as Render<{ 4 * 10 }, 4, 10>>::step(&mut waterfall);
+        }
+
+        let mut buffer = Buffer::new();
+        waterfall.render(0.0, 0.0, &mut buffer, Blend::Dest);
+        // The loud row has moved down two rows, fading as it went
+        assert_eq!(waterfall.rows[(waterfall.top + 2) % 10][0], 255);
+        assert!((200..255).contains(&waterfall.rows[waterfall.top][0]));
+        assert_eq!(buffer.get_pixel(3, 2).as_rgb8(), (0, 0, 0));
+        assert_ne!(buffer.get_pixel(0, 2).as_rgb8(), (0, 0, 0));
+    }
+}
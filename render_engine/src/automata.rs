@@ -0,0 +1,302 @@
+// Cellular automata: Conway's Life over the whole buffer and 1D elementary
+// rules scrolling down the drops. Both reseed themselves when the pattern
+// dies out or settles into a loop, so they run forever.
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::fixedcolor::FixedColor;
+use crate::render::Render;
+use crate::renderbuffer::{blend_merge, Blend};
+use crate::RenderBuffer;
+
+// Generations are slower than frames so the patterns can be followed
+const STEPS_PER_GENERATION: u32 = 4;
+// How many past generations are remembered to spot loops
+const HISTORY: usize = 8;
+// Generations to let a loop run before reseeding
+const STAGNANT_GENERATIONS: u32 = 30;
+// Chance that a cell starts alive when seeding
+const SEED_DENSITY: f32 = 0.35;
+
+// Spots a pattern that has died, frozen or fallen into a short cycle by
+// remembering hashes of the last few generations.
+struct Stagnation {
+    history: [u32; HISTORY],
+    next: usize,
+    filled: usize,
+    stagnant: u32,
+}
+
+impl Stagnation {
+    fn new() -> Self {
+        Self {
+            history: [0; HISTORY],
+            next: 0,
+            filled: 0,
+            stagnant: 0,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    // Record a generation, returning true once it is time to reseed
+    fn update(&mut self, hash: u32, population: usize) -> bool {
+        if population == 0 {
+            return true;
+        }
+        if self.history[..self.filled].contains(&hash) {
+            self.stagnant += 1;
+        } else {
+            self.stagnant = 0;
+        }
+        self.history[self.next] = hash;
+        self.next = (self.next + 1) % HISTORY;
+        self.filled = (self.filled + 1).min(HISTORY);
+        self.stagnant >= STAGNANT_GENERATIONS
+    }
+}
+
+// FNV-1a over the live cells
+fn hash_cells(cells: impl Iterator<Item = bool>) -> u32 {
+    cells.fold(0x811c9dc5, |hash, alive| (hash ^ alive as u32).wrapping_mul(0x01000193))
+}
+
+const LIFE_NEWBORN: FixedColor = FixedColor::GREEN;
+const LIFE_OLD: FixedColor = FixedColor::RED;
+// Age at which a cell is fully LIFE_OLD
+const LIFE_MAX_AGE: u8 = 32;
+
+pub struct Life<const X: usize, const Y: usize> {
+    // Age of every cell in generations, 0 is dead
+    cells: [[u8; X]; Y],
+    steps: u32,
+    stagnation: Stagnation,
+    rng: SmallRng,
+}
+
+impl<const X: usize, const Y: usize> Life<X, Y> {
+    pub fn new() -> Self {
+        let mut life = Self {
+            cells: [[0; X]; Y],
+            steps: 0,
+            stagnation: Stagnation::new(),
+            rng: SmallRng::seed_from_u64(0),
+        };
+        life.seed();
+        life
+    }
+
+    fn seed(&mut self) {
+        for row in self.cells.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = (self.rng.gen::<f32>() < SEED_DENSITY) as u8;
+            }
+        }
+        self.stagnation.reset();
+    }
+
+    fn neighbours(&self, x: usize, y: usize) -> usize {
+        let mut count = 0;
+        for dy in [Y - 1, 0, 1] {
+            for dx in [X - 1, 0, 1] {
+                if (dx, dy) != (0, 0) && self.cells[(y + dy) % Y][(x + dx) % X] > 0 {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    fn generation(&mut self) {
+        let next: [[u8; X]; Y] = core::array::from_fn(|y| {
+            core::array::from_fn(|x| {
+                let age = self.cells[y][x];
+                match (age > 0, self.neighbours(x, y)) {
+                    (true, 2) | (true, 3) => age.saturating_add(1),
+                    (false, 3) => 1,
+                    _ => 0,
+                }
+            })
+        });
+        self.cells = next;
+
+        let alive = || self.cells.iter().flatten().map(|age| *age > 0);
+        let population = alive().filter(|a| *a).count();
+        if self.stagnation.update(hash_cells(alive()), population) {
+            self.seed();
+        }
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Life<X, Y> {
+    fn step(&mut self) {
+        self.steps += 1;
+        if self.steps >= STEPS_PER_GENERATION {
+            self.steps = 0;
+            self.generation();
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, age) in row.iter().enumerate() {
+                if *age > 0 {
+                    let phase = (*age).min(LIFE_MAX_AGE) as f32 / LIFE_MAX_AGE as f32;
+                    buffer.safe_set_pixel(x as u32, y as u32, blend_merge(LIFE_NEWBORN, LIFE_OLD, phase));
+                }
+            }
+        }
+    }
+}
+
+// -----
+
+pub struct Elementary<const X: usize, const Y: usize> {
+    rule: u8,
+    // rows[0] is the newest generation at the top of the drops
+    rows: [[bool; X]; Y],
+    steps: u32,
+    stagnation: Stagnation,
+    rng: SmallRng,
+}
+
+impl<const X: usize, const Y: usize> Elementary<X, Y> {
+    pub fn new(rule: u8) -> Self {
+        let mut elementary = Self {
+            rule,
+            rows: [[false; X]; Y],
+            steps: 0,
+            stagnation: Stagnation::new(),
+            rng: SmallRng::seed_from_u64(0),
+        };
+        elementary.seed();
+        elementary
+    }
+
+    pub fn set_rule(&mut self, rule: u8) {
+        if rule != self.rule {
+            self.rule = rule;
+            self.rows = [[false; X]; Y];
+            self.seed();
+        }
+    }
+
+    fn seed(&mut self) {
+        for cell in self.rows[0].iter_mut() {
+            *cell = self.rng.gen::<f32>() < SEED_DENSITY;
+        }
+        self.stagnation.reset();
+    }
+
+    // Apply the rule to a row, wrapping around at the edges
+    fn next_row(rule: u8, row: &[bool; X]) -> [bool; X] {
+        core::array::from_fn(|x| {
+            let left = row[(x + X - 1) % X] as u8;
+            let centre = row[x] as u8;
+            let right = row[(x + 1) % X] as u8;
+            rule & (1 << ((left << 2) | (centre << 1) | right)) != 0
+        })
+    }
+
+    fn generation(&mut self) {
+        let next = Self::next_row(self.rule, &self.rows[0]);
+        self.rows.copy_within(0..Y - 1, 1);
+        self.rows[0] = next;
+
+        let population = next.iter().filter(|a| **a).count();
+        if self.stagnation.update(hash_cells(next.iter().copied()), population) {
+            self.seed();
+        }
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Elementary<X, Y> {
+    fn step(&mut self) {
+        self.steps += 1;
+        if self.steps >= STEPS_PER_GENERATION {
+            self.steps = 0;
+            self.generation();
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        for (y, row) in self.rows.iter().enumerate() {
+            // Older generations fade as they scroll down
+            let color = FixedColor::WHITE.scale(1.0 - y as f32 / Y as f32);
+            for (x, alive) in row.iter().enumerate() {
+                if *alive {
+                    buffer.safe_set_pixel(x as u32, y as u32, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_life_blinker() {
+        let mut life = Life::<5, 5>::new();
+        life.cells = [[0; 5]; 5];
+        life.cells[2][1] = 1;
+        life.cells[2][2] = 1;
+        life.cells[2][3] = 1;
+        life.generation();
+
+        let alive: [bool; 5] = core::array::from_fn(|y| life.cells[y][2] > 0);
+        assert_eq!(alive, [false, true, true, true, false]);
+        // The centre of the blinker survived and aged
+        assert_eq!(life.cells[2][2], 2);
+        assert_eq!(life.cells[2][1], 0);
+    }
+
+    #[test]
+    fn test_life_wraps() {
+        let mut life = Life::<4, 4>::new();
+        life.cells = [[0; 4]; 4];
+        life.cells[0][0] = 1;
+        life.cells[0][3] = 1;
+        life.cells[3][0] = 1;
+        assert_eq!(life.neighbours(3, 3), 3);
+    }
+
+    #[test]
+    fn test_life_reseeds_when_dead() {
+        let mut life = Life::<6, 6>::new();
+        life.cells = [[0; 6]; 6];
+        life.generation();
+        assert!(life.cells.iter().flatten().any(|age| *age > 0));
+    }
+
+    #[test]
+    fn test_elementary_rules() {
+        let row = [false, false, true, false, false];
+        assert_eq!(Elementary::<5, 1>::next_row(90, &row), [false, true, false, true, false]);
+        assert_eq!(Elementary::<5, 1>::next_row(30, &row), [false, true, true, true, false]);
+        assert_eq!(Elementary::<5, 1>::next_row(110, &row), [false, true, true, false, false]);
+    }
+
+    #[test]
+    fn test_elementary_scrolls_down() {
+        let mut elementary = Elementary::<5, 3>::new(30);
+        let top = elementary.rows[0];
+        elementary.generation();
+        assert_eq!(elementary.rows[1], top);
+    }
+
+    #[test]
+    fn test_stagnation_detects_loops() {
+        let mut stagnation = Stagnation::new();
+        // A blinker alternating between two states
+        let reseed = (0..=STAGNANT_GENERATIONS + 1).any(|i| stagnation.update(i % 2, 1));
+        assert!(reseed);
+
+        let mut stagnation = Stagnation::new();
+        assert!(!(0..100).any(|i| stagnation.update(i, 1)));
+    }
+}
@@ -0,0 +1,194 @@
+// A beat clock, so effects can keep time with the music playing in the yard
+// without hearing it. The tempo is set outright or tapped in, and the phase
+// runs from 0.0 on the beat to 1.0 just before the next one.
+
+use heapless::Deque;
+
+use crate::fixedcolor::FixedColor;
+use crate::render::Render;
+use crate::renderbuffer::Blend;
+use crate::RenderBuffer;
+
+pub const MIN_BPM: f32 = 30.0;
+pub const MAX_BPM: f32 = 300.0;
+// Intervals averaged for tap tempo
+const MAX_TAPS: usize = 8;
+// A longer gap between taps, in seconds, starts a new tempo
+const TAP_TIMEOUT: f32 = 2.0;
+
+// Where the music is, as seen by the effects for one frame
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Beat {
+    pub bpm: f32,
+    pub phase: f32,
+    // True for the first frame of every beat
+    pub on_beat: bool,
+    // Beats since the clock started, for effects that change every so many
+    pub count: u32,
+}
+
+#[derive(Default)]
+pub struct BeatClock {
+    // None while stopped
+    bpm: Option<f32>,
+    phase: f32,
+    count: u32,
+    on_beat: bool,
+    // Set by sync, so the next frame is on the beat
+    synced: bool,
+    // Seconds since the last tap, None before the first
+    since_tap: Option<f32>,
+    intervals: Deque<f32, MAX_TAPS>,
+}
+
+impl BeatClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bpm(&self) -> Option<f32> {
+        self.bpm
+    }
+
+    // None stops the clock
+    pub fn set_bpm(&mut self, bpm: Option<f32>) {
+        self.bpm = bpm.map(|bpm| bpm.clamp(MIN_BPM, MAX_BPM));
+    }
+
+    // Make now the start of a beat
+    pub fn sync(&mut self) {
+        self.phase = 0.0;
+        self.synced = true;
+    }
+
+    // Tap along with the music, the tempo follows the average of the last few
+    // taps and every tap lands on the beat
+    pub fn tap(&mut self) {
+        match self.since_tap {
+            Some(interval) if interval <= TAP_TIMEOUT => {
+                if self.intervals.is_full() {
+                    self.intervals.pop_front();
+                }
+                let _ = self.intervals.push_back(interval);
+            }
+            _ => self.intervals.clear(),
+        }
+        self.since_tap = Some(0.0);
+
+        if !self.intervals.is_empty() {
+            let average = self.intervals.iter().sum::<f32>() / self.intervals.len() as f32;
+            self.set_bpm(Some(60.0 / average));
+        }
+        self.sync();
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        let dt = dt.max(0.0);
+        self.since_tap = self.since_tap.map(|since| since + dt);
+        self.on_beat = core::mem::take(&mut self.synced);
+
+        if let Some(bpm) = self.bpm {
+            self.phase += dt * bpm / 60.0;
+            if self.phase >= 1.0 {
+                self.phase -= libm::floorf(self.phase);
+                self.on_beat = true;
+            }
+        }
+        if self.on_beat {
+            self.count = self.count.wrapping_add(1);
+        }
+    }
+
+    pub fn beat(&self) -> Option<Beat> {
+        self.bpm.map(|bpm| Beat {
+            bpm,
+            phase: self.phase,
+            on_beat: self.on_beat,
+            count: self.count,
+        })
+    }
+}
+
+// Turns around the colour wheel each beat
+const PULSE_HUE_STEP: f32 = 0.13;
+// Higher falls away more sharply after the beat
+const PULSE_SHARPNESS: f32 = 3.0;
+
+// Pulses the whole display on the beat, in a new colour every beat
+pub(crate) struct Pulse {
+    beat: Option<Beat>,
+}
+
+impl Pulse {
+    pub fn new() -> Self {
+        Self { beat: None }
+    }
+
+    pub fn set_beat(&mut self, beat: Option<Beat>) {
+        self.beat = beat;
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Pulse {
+    fn step(&mut self) {}
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        if let Some(beat) = self.beat {
+            let brightness = libm::powf(1.0 - beat.phase, PULSE_SHARPNESS);
+            let hue = (beat.count as f32 * PULSE_HUE_STEP) % 1.0;
+            buffer.clear_to_color(FixedColor::from_hsv(hue, 1.0, brightness));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(clock: &mut BeatClock, seconds: f32, dt: f32) -> u32 {
+        let mut beats = 0;
+        for _ in 0..libm::roundf(seconds / dt) as u32 {
+            clock.step(dt);
+            beats += clock.beat().is_some_and(|beat| beat.on_beat) as u32;
+        }
+        beats
+    }
+
+    #[test]
+    fn test_bpm() {
+        let mut clock = BeatClock::new();
+        assert_eq!(run(&mut clock, 2.0, 0.04), 0);
+        assert_eq!(clock.beat(), None);
+
+        clock.set_bpm(Some(120.0));
+        assert_eq!(run(&mut clock, 10.02, 0.04), 20);
+        assert!(clock.beat().unwrap().phase < 0.1);
+
+        clock.set_bpm(Some(1000.0));
+        assert_eq!(clock.bpm(), Some(MAX_BPM));
+    }
+
+    #[test]
+    fn test_tap_tempo() {
+        let mut clock = BeatClock::new();
+        for _ in 0..4 {
+            clock.tap();
+            run(&mut clock, 0.4, 0.01);
+        }
+        clock.tap();
+        let bpm = clock.bpm().unwrap();
+        assert!((bpm - 150.0).abs() < 1.0, "{}", bpm);
+
+        // The tap is on the beat
+        clock.step(0.01);
+        assert!(clock.beat().unwrap().on_beat);
+
+        // After a pause, the next taps start a new tempo
+        run(&mut clock, 3.0, 0.01);
+        clock.tap();
+        run(&mut clock, 1.0, 0.01);
+        clock.tap();
+        let bpm = clock.bpm().unwrap();
+        assert!((bpm - 60.0).abs() < 1.0, "{}", bpm);
+    }
+}
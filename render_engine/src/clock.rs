@@ -0,0 +1,117 @@
+// Wall-clock time for renderers that need the date, kept separately from the
+// effect time passed to `render`. Devices have no RTC, so the host sets the
+// time over the command protocol and the engine advances it every frame.
+//
+// Times are local seconds since 1970-01-01 00:00, i.e. the host applies its
+// timezone before sending them.
+
+pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    pub const fn new(year: i32, month: u8, day: u8) -> Self {
+        Self { year, month, day }
+    }
+
+    // Days since 1970-01-01, using Howard Hinnant's days_from_civil
+    pub fn to_days(&self) -> i64 {
+        let y = self.year as i64 - (self.month <= 2) as i64;
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let m = self.month as i64;
+        let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    pub fn from_days(days: i64) -> Self {
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+        let year = (yoe + era * 400 + (month <= 2) as i64) as i32;
+        Self { year, month, day }
+    }
+
+    // Midnight at the start of the day, in seconds since 1970
+    pub fn to_seconds(&self) -> u64 {
+        self.to_days().max(0) as u64 * SECONDS_PER_DAY
+    }
+
+    pub fn from_seconds(seconds: u64) -> Self {
+        Self::from_days((seconds / SECONDS_PER_DAY) as i64)
+    }
+}
+
+#[derive(Default)]
+pub struct WallClock {
+    // Whole seconds, plus the fraction of a second accumulated since
+    seconds: Option<u64>,
+    fraction: f32,
+}
+
+impl WallClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, seconds: u64) {
+        self.seconds = Some(seconds);
+        self.fraction = 0.0;
+    }
+
+    // None until the time has been set
+    pub fn now(&self) -> Option<u64> {
+        self.seconds
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        if let Some(seconds) = &mut self.seconds {
+            self.fraction += dt.max(0.0);
+            let whole = libm::floorf(self.fraction);
+            *seconds += whole as u64;
+            self.fraction -= whole;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date_round_trip() {
+        assert_eq!(Date::new(1970, 1, 1).to_days(), 0);
+        assert_eq!(Date::new(2000, 3, 1).to_days(), 11017);
+        assert_eq!(Date::new(2024, 12, 25).to_seconds(), 1735084800);
+        for days in (-800..80000).step_by(97) {
+            assert_eq!(Date::from_days(days).to_days(), days);
+        }
+        assert_eq!(Date::from_days(Date::new(2024, 2, 29).to_days() + 1), Date::new(2024, 3, 1));
+    }
+
+    #[test]
+    fn test_wall_clock() {
+        let mut clock = WallClock::new();
+        clock.step(5.0);
+        assert_eq!(clock.now(), None);
+
+        clock.set(100);
+        for _ in 0..25 {
+            clock.step(0.04);
+        }
+        assert!(matches!(clock.now(), Some(100) | Some(101)));
+        clock.step(0.5);
+        assert_eq!(clock.now(), Some(101));
+    }
+}
@@ -0,0 +1,226 @@
+// Counts down the days, hours and minutes to a date, Christmas by default,
+// then celebrates with fireworks once it arrives.
+
+use core::fmt::Write;
+
+use crate::clock::{Date, SECONDS_PER_DAY};
+use crate::fixedcolor::FixedColor;
+use crate::font::{self, Orientation, TextColor, TextStyle, GLYPH_HEIGHT};
+use crate::render::{Fireworks, Render};
+use crate::renderbuffer::Blend;
+use crate::RenderBuffer;
+
+// Steps each part is shown for when the display is too narrow for all of it
+const STEPS_PER_PART: u32 = 50;
+const LARGEST_SCALE: u32 = 8;
+const COUNTDOWN_COLORS: [FixedColor; 3] = [FixedColor::RED, FixedColor::GREEN, FixedColor::WHITE];
+// Shown while the time has not been set
+const UNKNOWN: &str = "--:--";
+
+type Line = heapless::String<16>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    Unknown,
+    Counting(u64),
+    Celebrating,
+}
+
+pub struct Countdown<const X: usize, const Y: usize> {
+    now: Option<u64>,
+    // None counts down to the next Christmas
+    target: Option<Date>,
+    fireworks: Fireworks<X, Y>,
+    steps: u32,
+}
+
+impl<const X: usize, const Y: usize> Countdown<X, Y> {
+    pub fn new() -> Self {
+        Self {
+            now: None,
+            target: None,
+            fireworks: Fireworks::new(),
+            steps: 0,
+        }
+    }
+
+    pub fn set_now(&mut self, now: Option<u64>) {
+        self.now = now;
+    }
+
+    pub fn set_target(&mut self, target: Option<Date>) {
+        self.target = target;
+    }
+
+    fn state(&self) -> State {
+        let Some(now) = self.now else {
+            return State::Unknown;
+        };
+
+        match self.target {
+            Some(target) => match target.to_seconds().checked_sub(now) {
+                Some(remaining) if remaining > 0 => State::Counting(remaining),
+                _ => State::Celebrating,
+            },
+            None => {
+                // Celebrate all of Christmas day, then start on next year's
+                let year = Date::from_seconds(now).year;
+                let christmas = Date::new(year, 12, 25).to_seconds();
+                if now < christmas {
+                    State::Counting(christmas - now)
+                } else if now < christmas + SECONDS_PER_DAY {
+                    State::Celebrating
+                } else {
+                    State::Counting(Date::new(year + 1, 12, 25).to_seconds() - now)
+                }
+            }
+        }
+    }
+
+    // Days, then hours and minutes
+    fn lines(remaining: u64) -> heapless::Vec<Line, 2> {
+        let minutes = remaining.div_ceil(60);
+        let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+
+        let mut lines = heapless::Vec::new();
+        let mut line = Line::new();
+        if days > 0 {
+            let _ = write!(line, "{}D", days);
+            let _ = lines.push(line.clone());
+            line.clear();
+        }
+        let _ = write!(line, "{:02}:{:02}", hours, minutes);
+        let _ = lines.push(line);
+        lines
+    }
+
+    // One short part at a time, for a handful of drops
+    fn part(remaining: u64, index: u32) -> Line {
+        let minutes = remaining.div_ceil(60);
+        let mut line = Line::new();
+        let _ = match index % 3 {
+            0 => write!(line, "{}D", minutes / (24 * 60)),
+            1 => write!(line, "{}H", minutes / 60 % 24),
+            _ => write!(line, "{}M", minutes % 60),
+        };
+        line
+    }
+
+    // The largest scale that fits all the lines, if any does
+    fn fit(lines: &[Line]) -> Option<u32> {
+        (1..=LARGEST_SCALE).rev().find(|scale| {
+            let height = lines.len() as u32 * (GLYPH_HEIGHT + 1) * scale - scale;
+            height <= Y as u32 && lines.iter().all(|line| font::text_width(line, *scale) <= X as u32)
+        })
+    }
+
+    fn draw_lines<const S: usize>(buffer: &mut RenderBuffer<S, X, Y>, lines: &[Line], scale: u32) {
+        let line_height = (GLYPH_HEIGHT + 1) * scale;
+        let height = lines.len() as u32 * line_height - scale;
+        let mut y = (Y as i32 - height as i32) / 2;
+        for (index, line) in lines.iter().enumerate() {
+            let color = TextColor::Solid(COUNTDOWN_COLORS[index % COUNTDOWN_COLORS.len()]);
+            let style = TextStyle::new(color).with_scale(scale);
+            let x = (X as i32 - font::text_width(line, scale) as i32) / 2;
+            font::draw_text(buffer, line, x, y, &style);
+            y += line_height as i32;
+        }
+    }
+
+    fn draw_vertical<const S: usize>(buffer: &mut RenderBuffer<S, X, Y>, line: &str, color: FixedColor) {
+        let scale = TextStyle::scale_for_height(X as u32);
+        let style = TextStyle::new(TextColor::Solid(color))
+            .with_scale(scale)
+            .with_orientation(Orientation::Vertical);
+        let x = (X as i32 - (GLYPH_HEIGHT * scale) as i32) / 2;
+        let y = (Y as i32 - font::text_width(line, scale) as i32) / 2;
+        font::draw_text(buffer, line, x, y, &style);
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Countdown<X, Y> {
+    fn step(&mut self) {
+        self.steps = self.steps.wrapping_add(1);
+        if self.state() == State::Celebrating {
+            <Fireworks<X, Y> as Render<S, X, Y>>::step(&mut self.fireworks);
+        }
+    }
+
+    fn render(&self, t: f32, dt: f32, buffer: &mut RenderBuffer<S, X, Y>, blend: Blend) {
+        match self.state() {
+            State::Celebrating => self.fireworks.render(t, dt, buffer, blend),
+            State::Unknown => {
+                let lines = [Line::try_from(UNKNOWN).unwrap_or_default()];
+                match Self::fit(&lines) {
+                    Some(scale) => Self::draw_lines(buffer, &lines, scale),
+                    None => Self::draw_vertical(buffer, UNKNOWN, FixedColor::WHITE),
+                }
+            }
+            State::Counting(remaining) => {
+                let lines = Self::lines(remaining);
+                match Self::fit(&lines) {
+                    Some(scale) => Self::draw_lines(buffer, &lines, scale),
+                    None => {
+                        let index = self.steps / STEPS_PER_PART;
+                        let color = COUNTDOWN_COLORS[(index % 3) as usize];
+                        Self::draw_vertical(buffer, &Self::part(remaining, index), color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHRISTMAS_2026: u64 = 1798156800;
+
+    #[test]
+    fn test_counts_down_to_christmas() {
+        let mut countdown = Countdown::<50, 24>::new();
+        assert_eq!(countdown.state(), State::Unknown);
+
+        countdown.set_now(Some(CHRISTMAS_2026 - 90));
+        assert_eq!(countdown.state(), State::Counting(90));
+        countdown.set_now(Some(CHRISTMAS_2026 + 3600));
+        assert_eq!(countdown.state(), State::Celebrating);
+        countdown.set_now(Some(CHRISTMAS_2026 + SECONDS_PER_DAY));
+        assert!(matches!(countdown.state(), State::Counting(r) if r == 364 * SECONDS_PER_DAY));
+    }
+
+    #[test]
+    fn test_custom_target() {
+        let mut countdown = Countdown::<50, 24>::new();
+        countdown.set_target(Some(Date::new(2027, 1, 1)));
+        countdown.set_now(Some(CHRISTMAS_2026));
+        assert_eq!(countdown.state(), State::Counting(7 * SECONDS_PER_DAY));
+        countdown.set_now(Some(CHRISTMAS_2026 + 30 * SECONDS_PER_DAY));
+        assert_eq!(countdown.state(), State::Celebrating);
+    }
+
+    #[test]
+    fn test_lines() {
+        let lines = Countdown::<50, 24>::lines(3 * SECONDS_PER_DAY + 4 * 3600 + 5 * 60);
+        assert_eq!(lines.as_slice(), ["3D", "04:05"]);
+        // Part minutes round up, so the display hits zero exactly on time
+        let lines = Countdown::<50, 24>::lines(59);
+        assert_eq!(lines.as_slice(), ["00:01"]);
+        assert_eq!(Countdown::<5, 24>::part(2 * 3600, 1), "2H");
+    }
+
+    #[test]
+    fn test_layout() {
+        let lines = Countdown::<50, 24>::lines(24 * SECONDS_PER_DAY);
+        assert_eq!(Countdown::<50, 24>::fit(&lines), Some(2));
+        assert_eq!(Countdown::<5, 24>::fit(&lines), None);
+
+        type Buffer = RenderBuffer<{ 5 * 24 }, 5, 24>;
+        let mut countdown = Countdown::<5, 24>::new();
+        countdown.set_now(Some(CHRISTMAS_2026 - 3 * SECONDS_PER_DAY));
+        let mut buffer = Buffer::new();
+        countdown.render(0.0, 0.0, &mut buffer, Blend::Dest);
+        assert!(buffer.buffer().iter().any(|p| p.as_rgb8() != (0, 0, 0)));
+    }
+}
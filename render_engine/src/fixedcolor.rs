@@ -22,8 +22,12 @@ impl FixedColor {
         b: ONE,
         a: ONE,
     };
-    
-    pub fn rgb(r: T, g: T, b: T) -> Self {
+    pub const BLACK: Self = Self::rgb(ZERO, ZERO, ZERO);
+    pub const RED: Self = Self::rgb(ONE, ZERO, ZERO);
+    pub const GREEN: Self = Self::rgb(ZERO, ONE, ZERO);
+    pub const BLUE: Self = Self::rgb(ZERO, ZERO, ONE);
+
+    pub const fn rgb(r: T, g: T, b: T) -> Self {
         Self { r, g, b, a: ONE }
     }
 
@@ -53,6 +57,31 @@ impl FixedColor {
         }
     }
 
+    // Hue in turns, so 0.0 and 1.0 are both red
+    pub fn from_hsv(h: T, s: T, v: T) -> Self {
+        let h = (h - libm::floorf(h)) * 6.0;
+        let sector = h as u32;
+        let f = h - sector as T;
+        let p = v * (ONE - s);
+        let q = v * (ONE - s * f);
+        let t = v * (ONE - s * (ONE - f));
+        match sector {
+            0 => Self::rgb(v, t, p),
+            1 => Self::rgb(q, v, p),
+            2 => Self::rgb(p, v, t),
+            3 => Self::rgb(p, q, v),
+            4 => Self::rgb(t, p, v),
+            _ => Self::rgb(v, p, q),
+        }
+    }
+
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self {
+            a: (a as f32) / MAX_U8,
+            ..Self::from_rgb8(r, g, b)
+        }
+    }
+
     pub fn scale(&self, scale: T) -> Self {
         Self {
             r: (self.r * scale).clamp(ZERO, ONE),
@@ -61,6 +90,17 @@ impl FixedColor {
             a: self.a,
         }
     }
+
+    // Component-wise product, used to tint a colour
+    pub fn multiply(&self, other: Self) -> Self {
+        Self {
+            r: self.r * other.r,
+            g: self.g * other.g,
+            b: self.b * other.b,
+            a: self.a,
+        }
+    }
+
     pub fn saturating_add(&self, other: Self) -> Self {
         Self {
             r: (self.r + other.r).clamp(ZERO, ONE),
@@ -82,6 +122,13 @@ mod tests {
         assert_eq!(c.as_rgb8(), (255, 255, 255));
     }
 
+    #[test]
+    fn test_from_hsv() {
+        assert_eq!(FixedColor::from_hsv(0.0, 1.0, 1.0).as_rgb8(), (255, 0, 0));
+        assert_eq!(FixedColor::from_hsv(1.0 / 3.0, 1.0, 1.0).as_rgb8(), (0, 255, 0));
+        assert_eq!(FixedColor::from_hsv(1.5, 0.0, 0.5).as_rgb8(), (127, 127, 127));
+    }
+
     // run this test if the serde feature is enabled
 
     #[cfg(feature = "serde")]
@@ -0,0 +1,254 @@
+// A compact bitmap font for scrolling text across the drops.
+//
+// Glyphs are 5 pixels high and stored column by column, bit 0 being the top
+// row. They can be scaled up to fill taller buffers (e.g. x4 on 24 LEDs) and
+// drawn horizontally, or rotated to read down a single drop.
+
+use crate::fixedcolor::FixedColor;
+use crate::RenderBuffer;
+
+pub const GLYPH_HEIGHT: u32 = 5;
+const GLYPH_SPACING: u32 = 1;
+
+pub fn glyph(c: char) -> &'static [u8] {
+    match c.to_ascii_uppercase() {
+        'A' => &[0b11110, 0b00101, 0b11110],
+        'B' => &[0b11111, 0b10101, 0b01010],
+        'C' => &[0b01110, 0b10001, 0b10001],
+        'D' => &[0b11111, 0b10001, 0b01110],
+        'E' => &[0b11111, 0b10101, 0b10001],
+        'F' => &[0b11111, 0b00101, 0b00001],
+        'G' => &[0b01110, 0b10001, 0b11101],
+        'H' => &[0b11111, 0b00100, 0b11111],
+        'I' => &[0b10001, 0b11111, 0b10001],
+        'J' => &[0b01000, 0b10000, 0b01111],
+        'K' => &[0b11111, 0b00100, 0b11011],
+        'L' => &[0b11111, 0b10000, 0b10000],
+        'M' => &[0b11111, 0b00010, 0b00100, 0b00010, 0b11111],
+        'N' => &[0b11111, 0b00010, 0b00100, 0b11111],
+        'O' => &[0b01110, 0b10001, 0b01110],
+        'P' => &[0b11111, 0b00101, 0b00010],
+        'Q' => &[0b01110, 0b10001, 0b01110, 0b10000],
+        'R' => &[0b11111, 0b00101, 0b11010],
+        'S' => &[0b10010, 0b10101, 0b01001],
+        'T' => &[0b00001, 0b11111, 0b00001],
+        'U' => &[0b11111, 0b10000, 0b11111],
+        'V' => &[0b01111, 0b10000, 0b01111],
+        'W' => &[0b11111, 0b01000, 0b00100, 0b01000, 0b11111],
+        'X' => &[0b11011, 0b00100, 0b11011],
+        'Y' => &[0b00011, 0b11100, 0b00011],
+        'Z' => &[0b11001, 0b10101, 0b10011],
+        '0' => &[0b11111, 0b10001, 0b11111],
+        '1' => &[0b10010, 0b11111, 0b10000],
+        '2' => &[0b11001, 0b10101, 0b10010],
+        '3' => &[0b10001, 0b10101, 0b01010],
+        '4' => &[0b00111, 0b00100, 0b11111],
+        '5' => &[0b10111, 0b10101, 0b01001],
+        '6' => &[0b11110, 0b10101, 0b11101],
+        '7' => &[0b00001, 0b11101, 0b00011],
+        '8' => &[0b11111, 0b10101, 0b11111],
+        '9' => &[0b10111, 0b10101, 0b01111],
+        ' ' => &[0b00000, 0b00000],
+        '!' => &[0b10111],
+        '.' => &[0b10000],
+        ',' => &[0b10000, 0b01000],
+        ':' => &[0b01010],
+        '-' => &[0b00100, 0b00100, 0b00100],
+        '+' => &[0b00100, 0b01110, 0b00100],
+        '\'' => &[0b00011],
+        '?' => &[0b00001, 0b10101, 0b00010],
+        '/' => &[0b11000, 0b00100, 0b00011],
+        '*' => &[0b00101, 0b00010, 0b00101],
+        // Anything we don't have a glyph for
+        _ => &[0b00001, 0b10101, 0b00010],
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Orientation {
+    // Left to right, glyphs upright
+    Horizontal,
+    // Top to bottom, glyphs rotated clockwise to read down a drop
+    Vertical,
+}
+
+#[derive(Clone, Copy)]
+pub enum TextColor<'a> {
+    Solid(FixedColor),
+    // Cycles through the palette, one colour per glyph
+    Palette(&'a [FixedColor]),
+}
+
+impl TextColor<'_> {
+    fn for_glyph(&self, index: usize) -> FixedColor {
+        match self {
+            TextColor::Solid(color) => *color,
+            TextColor::Palette(palette) if !palette.is_empty() => palette[index % palette.len()],
+            TextColor::Palette(_) => FixedColor::WHITE,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TextStyle<'a> {
+    pub color: TextColor<'a>,
+    pub scale: u32,
+    pub orientation: Orientation,
+}
+
+impl<'a> TextStyle<'a> {
+    pub fn new(color: TextColor<'a>) -> Self {
+        Self {
+            color,
+            scale: 1,
+            orientation: Orientation::Horizontal,
+        }
+    }
+
+    pub fn with_scale(self, scale: u32) -> Self {
+        Self { scale: scale.max(1), ..self }
+    }
+
+    pub fn with_orientation(self, orientation: Orientation) -> Self {
+        Self { orientation, ..self }
+    }
+
+    // The largest scale at which a glyph still fits in `height` pixels
+    pub fn scale_for_height(height: u32) -> u32 {
+        (height / GLYPH_HEIGHT).max(1)
+    }
+}
+
+// Length of the text along the reading direction, in pixels
+pub fn text_width(text: &str, scale: u32) -> u32 {
+    let columns: u32 = text
+        .chars()
+        .map(|c| glyph(c).len() as u32 + GLYPH_SPACING)
+        .sum();
+    columns.saturating_sub(GLYPH_SPACING) * scale
+}
+
+// Draw `text` with its top left corner at (x, y), clipping anything off the
+// buffer. Returns the number of pixels advanced along the reading direction.
+pub fn draw_text<const S: usize, const X: usize, const Y: usize>(
+    buffer: &mut RenderBuffer<S, X, Y>,
+    text: &str,
+    x: i32,
+    y: i32,
+    style: &TextStyle,
+) -> u32 {
+    let scale = style.scale.max(1) as i32;
+    let size = buffer.size();
+    let (width, height) = (size.x as i32, size.y as i32);
+    let mut advance = 0i32;
+
+    for (index, c) in text.chars().enumerate() {
+        let color = style.color.for_glyph(index);
+        let columns = glyph(c);
+
+        // Glyphs wholly off the buffer along the reading direction are skipped,
+        // so a huge scale costs no more than the pixels it can reach
+        let length = columns.len() as i32 * scale;
+        let (start, limit) = match style.orientation {
+            Orientation::Horizontal => (x + advance, width),
+            Orientation::Vertical => (y + advance, height),
+        };
+        if start + length <= 0 || start >= limit {
+            advance += (columns.len() as u32 + GLYPH_SPACING) as i32 * scale;
+            continue;
+        }
+
+        for (column, bits) in columns.iter().enumerate() {
+            for row in 0..GLYPH_HEIGHT as i32 {
+                if bits & (1 << row) == 0 {
+                    continue;
+                }
+                let u = advance + column as i32 * scale;
+                let v = row * scale;
+                let (px, py) = match style.orientation {
+                    Orientation::Horizontal => (x + u, y + v),
+                    Orientation::Vertical => (x + (GLYPH_HEIGHT as i32 - 1) * scale - v, y + u),
+                };
+                // Only the part of the scaled block on the buffer
+                for by in py.max(0)..(py + scale).min(height) {
+                    for bx in px.max(0)..(px + scale).min(width) {
+                        buffer.safe_set_pixel(bx as u32, by as u32, color);
+                    }
+                }
+            }
+        }
+        advance += (columns.len() as u32 + GLYPH_SPACING) as i32 * scale;
+    }
+
+    (advance as u32).saturating_sub(GLYPH_SPACING * scale as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Buffer = RenderBuffer<{ 12 * 5 }, 12, 5>;
+
+    fn lit(buffer: &Buffer) -> usize {
+        buffer.buffer().iter().filter(|p| p.as_rgb8() != (0, 0, 0)).count()
+    }
+
+    #[test]
+    fn test_text_width() {
+        assert_eq!(text_width("", 1), 0);
+        assert_eq!(text_width("HI", 1), 7);
+        assert_eq!(text_width("HI", 4), 28);
+    }
+
+    #[test]
+    fn test_draw_horizontal() {
+        let mut buffer = Buffer::new();
+        let style = TextStyle::new(TextColor::Solid(FixedColor::RED));
+        let advance = draw_text(&mut buffer, "L", 0, 0, &style);
+
+        assert_eq!(advance, 3);
+        assert_eq!(lit(&buffer), 7);
+        assert_eq!(buffer.get_pixel(0, 0).as_rgb8(), (255, 0, 0));
+        assert_eq!(buffer.get_pixel(2, 4).as_rgb8(), (255, 0, 0));
+        assert_eq!(buffer.get_pixel(2, 0).as_rgb8(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_draw_clips_and_palette() {
+        let mut buffer = Buffer::new();
+        let palette = [FixedColor::RED, FixedColor::GREEN];
+        let style = TextStyle::new(TextColor::Palette(&palette));
+        draw_text(&mut buffer, "LL", -2, 0, &style);
+
+        // Only the foot of the first L is left on the buffer
+        assert_eq!(buffer.get_pixel(0, 4).as_rgb8(), (255, 0, 0));
+        assert_eq!(buffer.get_pixel(2, 0).as_rgb8(), (0, 255, 0));
+    }
+
+    #[test]
+    fn test_draw_vertical() {
+        type Drop = RenderBuffer<{ 5 * 12 }, 5, 12>;
+        let mut buffer = Drop::new();
+        let style = TextStyle::new(TextColor::Solid(FixedColor::WHITE)).with_orientation(Orientation::Vertical);
+        draw_text(&mut buffer, "L", 0, 0, &style);
+
+        // The upright of the L runs across the top of the drop
+        for x in 0..5 {
+            assert_eq!(buffer.get_pixel(x, 0).as_rgb8(), (255, 255, 255));
+        }
+        assert_eq!(buffer.get_pixel(0, 2).as_rgb8(), (255, 255, 255));
+        assert_eq!(buffer.get_pixel(4, 2).as_rgb8(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_draw_huge_scale_is_clipped() {
+        let mut buffer = Buffer::new();
+        let style = TextStyle::new(TextColor::Solid(FixedColor::RED)).with_scale(u8::MAX as u32);
+        let text = "MW".repeat(32);
+        let advance = draw_text(&mut buffer, &text, 0, 0, &style);
+
+        // The first column of the M covers the whole buffer and nothing else is drawn
+        assert_eq!(advance, text_width(&text, u8::MAX as u32));
+        assert_eq!(lit(&buffer), 12 * 5);
+    }
+}
@@ -0,0 +1,477 @@
+// Games for the kids to play on the lights. Input arrives through
+// `RenderEngine::input` from whatever is acting as the controller, and the
+// games advance one move per so many steps like the other effects.
+
+use heapless::Deque;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::fixedcolor::FixedColor;
+use crate::render::Render;
+use crate::renderbuffer::Blend;
+use crate::RenderBuffer;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Input {
+    Up,
+    Down,
+    Left,
+    Right,
+    Button,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn opposite(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+        }
+    }
+
+    fn offset(self) -> (i16, i16) {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+}
+
+// Steps per flash when a game is over
+const FLASH_STEPS: u32 = 5;
+// Steps the result is shown for before a new game starts
+const GAME_OVER_STEPS: u32 = 60;
+
+// Longest the snake can grow, in cells
+const MAX_SNAKE: usize = 128;
+const SNAKE_STEPS_PER_MOVE: u32 = 4;
+const SNAKE_START_LENGTH: u32 = 3;
+// Cells the snake grows by for each piece of food
+const SNAKE_GROWTH: u32 = 2;
+const SNAKE_COLOR: FixedColor = FixedColor::rgb(0.0, 0.6, 0.0);
+const SNAKE_HEAD_COLOR: FixedColor = FixedColor::rgb(0.4, 1.0, 0.2);
+const FOOD_COLOR: FixedColor = FixedColor::RED;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SnakeState {
+    Playing,
+    Paused,
+    Over(u32),
+}
+
+// The board wraps at the edges, so only running into itself ends the game
+pub struct Snake<const X: usize, const Y: usize> {
+    // Head first
+    body: Deque<(i16, i16), MAX_SNAKE>,
+    heading: Direction,
+    // Applied on the next move, so two quick presses cannot reverse the snake
+    next: Direction,
+    grow: u32,
+    food: (i16, i16),
+    state: SnakeState,
+    steps: u32,
+    rng: SmallRng,
+}
+
+impl<const X: usize, const Y: usize> Snake<X, Y> {
+    pub fn new() -> Self {
+        let mut snake = Self {
+            body: Deque::new(),
+            heading: Direction::Right,
+            next: Direction::Right,
+            grow: 0,
+            food: (0, 0),
+            state: SnakeState::Playing,
+            steps: 0,
+            rng: SmallRng::seed_from_u64(0),
+        };
+        snake.reset();
+        snake
+    }
+
+    fn reset(&mut self) {
+        // Head along the longer side of the display
+        let heading = if X >= Y { Direction::Right } else { Direction::Down };
+        self.body.clear();
+        let _ = self.body.push_front(((X / 2) as i16, (Y / 2) as i16));
+        self.heading = heading;
+        self.next = heading;
+        self.grow = SNAKE_START_LENGTH - 1;
+        self.state = SnakeState::Playing;
+        self.place_food();
+    }
+
+    fn place_food(&mut self) {
+        // Give up looking for a free cell eventually, the snake just eats it sooner
+        for _ in 0..64 {
+            self.food = (self.rng.gen_range(0..X as i16), self.rng.gen_range(0..Y as i16));
+            if !self.body.iter().any(|&cell| cell == self.food) {
+                break;
+            }
+        }
+    }
+
+    pub fn input(&mut self, input: Input) {
+        match input {
+            Input::Up => self.next = Direction::Up,
+            Input::Down => self.next = Direction::Down,
+            Input::Left => self.next = Direction::Left,
+            Input::Right => self.next = Direction::Right,
+            Input::Button => match self.state {
+                SnakeState::Playing => self.state = SnakeState::Paused,
+                SnakeState::Paused => self.state = SnakeState::Playing,
+                SnakeState::Over(_) => self.reset(),
+            },
+        }
+    }
+
+    fn advance(&mut self) {
+        if self.next != self.heading.opposite() {
+            self.heading = self.next;
+        }
+
+        let Some(&(x, y)) = self.body.front() else {
+            return;
+        };
+        let (dx, dy) = self.heading.offset();
+        let head = ((x + dx).rem_euclid(X as i16), (y + dy).rem_euclid(Y as i16));
+
+        // The tail moves out of the way unless the snake is growing
+        let growing = self.grow > 0 && !self.body.is_full();
+        let tail = if growing { self.body.len() } else { self.body.len() - 1 };
+        if self.body.iter().take(tail).any(|&cell| cell == head) {
+            self.state = SnakeState::Over(GAME_OVER_STEPS);
+            return;
+        }
+
+        if growing {
+            self.grow -= 1;
+        } else {
+            self.body.pop_back();
+        }
+        let _ = self.body.push_front(head);
+
+        if head == self.food {
+            self.grow += SNAKE_GROWTH;
+            self.place_food();
+        }
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Snake<X, Y> {
+    fn step(&mut self) {
+        self.steps += 1;
+        match self.state {
+            SnakeState::Playing => {
+                if self.steps >= SNAKE_STEPS_PER_MOVE {
+                    self.steps = 0;
+                    self.advance();
+                }
+            }
+            SnakeState::Paused => {}
+            SnakeState::Over(0) => self.reset(),
+            SnakeState::Over(n) => self.state = SnakeState::Over(n - 1),
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        let (body, head) = match self.state {
+            SnakeState::Over(n) if (n / FLASH_STEPS) & 1 == 0 => (FixedColor::RED, FixedColor::RED),
+            _ => (SNAKE_COLOR, SNAKE_HEAD_COLOR),
+        };
+
+        buffer.safe_set_pixel(self.food.0 as u32, self.food.1 as u32, FOOD_COLOR);
+        for (index, &(x, y)) in self.body.iter().enumerate() {
+            let color = if index == 0 { head } else { body };
+            buffer.safe_set_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+// Points needed to win a game of Pong
+const PONG_WINNING_SCORE: u8 = 5;
+// Steps before the ball is served, unless the server presses the button
+const PONG_SERVE_STEPS: u32 = 40;
+// Cells per step along the court, and the most it speeds up to
+const PONG_BALL_SPEED: f32 = 0.3;
+const PONG_MAX_SPEED: f32 = 0.9;
+const PONG_SPEED_UP: f32 = 1.05;
+// Sideways speed added per cell the ball hits away from the paddle centre
+const PONG_ENGLISH: f32 = 0.12;
+const PONG_MAX_SPIN: f32 = 0.5;
+const PONG_COLORS: [FixedColor; 2] = [FixedColor::BLUE, FixedColor::RED];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum PongState {
+    // Waiting to serve towards the given player
+    Serving { steps: u32, to: usize },
+    Playing,
+    Won { player: usize, steps: u32 },
+}
+
+// Two player Pong, with the paddles at either end of the longer side of the
+// display. Player 0 is at the left (or top) and player 1 at the right (or bottom).
+pub struct Pong<const X: usize, const Y: usize> {
+    // Top (or left) cell of each paddle, across the court
+    paddles: [i32; 2],
+    // Position and velocity, as (along, across) the court
+    ball: (f32, f32),
+    velocity: (f32, f32),
+    scores: [u8; 2],
+    state: PongState,
+    rng: SmallRng,
+}
+
+impl<const X: usize, const Y: usize> Pong<X, Y> {
+    const LENGTH: i32 = if X >= Y { X as i32 } else { Y as i32 };
+    const WIDTH: i32 = if X >= Y { Y as i32 } else { X as i32 };
+    const PADDLE: i32 = if Self::WIDTH / 3 > 2 { Self::WIDTH / 3 } else { 2 };
+
+    pub fn new() -> Self {
+        let centre = (Self::WIDTH - Self::PADDLE) / 2;
+        Self {
+            paddles: [centre; 2],
+            ball: Self::centre(),
+            velocity: (0.0, 0.0),
+            scores: [0; 2],
+            state: PongState::Serving { steps: PONG_SERVE_STEPS, to: 1 },
+            rng: SmallRng::seed_from_u64(0),
+        }
+    }
+
+    fn centre() -> (f32, f32) {
+        ((Self::LENGTH - 1) as f32 / 2.0, (Self::WIDTH - 1) as f32 / 2.0)
+    }
+
+    pub fn input(&mut self, player: u8, input: Input) {
+        let Some(paddle) = self.paddles.get_mut(player as usize) else {
+            return;
+        };
+        match input {
+            Input::Up | Input::Left => *paddle = (*paddle - 1).max(0),
+            Input::Down | Input::Right => *paddle = (*paddle + 1).min(Self::WIDTH - Self::PADDLE),
+            Input::Button => {
+                if let PongState::Serving { steps, .. } = &mut self.state {
+                    *steps = 0;
+                }
+            }
+        }
+    }
+
+    fn serve(&mut self, to: usize) {
+        self.ball = Self::centre();
+        let along = if to == 0 { -PONG_BALL_SPEED } else { PONG_BALL_SPEED };
+        self.velocity = (along, self.rng.gen_range(-0.2..0.2));
+        self.state = PongState::Playing;
+    }
+
+    fn point(&mut self, player: usize) {
+        self.scores[player] += 1;
+        self.state = if self.scores[player] >= PONG_WINNING_SCORE {
+            PongState::Won { player, steps: GAME_OVER_STEPS }
+        } else {
+            // The player who missed gets the next serve
+            PongState::Serving { steps: PONG_SERVE_STEPS, to: 1 - player }
+        };
+    }
+
+    // Bounce off the paddle at an end of the court, if it is in the way
+    fn hit(&mut self, player: usize) -> bool {
+        let across = libm::roundf(self.ball.1) as i32;
+        let paddle = self.paddles[player];
+        if across < paddle || across >= paddle + Self::PADDLE {
+            return false;
+        }
+
+        let offset = self.ball.1 - (paddle as f32 + (Self::PADDLE - 1) as f32 / 2.0);
+        let speed = (self.velocity.0.abs() * PONG_SPEED_UP).min(PONG_MAX_SPEED);
+        let along = if player == 0 { speed } else { -speed };
+        let spin = (self.velocity.1 + offset * PONG_ENGLISH).clamp(-PONG_MAX_SPIN, PONG_MAX_SPIN);
+        self.velocity = (along, spin);
+        true
+    }
+
+    fn advance(&mut self) {
+        let (mut along, mut across) = (self.ball.0 + self.velocity.0, self.ball.1 + self.velocity.1);
+
+        // Bounce off the sides
+        let side = (Self::WIDTH - 1) as f32;
+        if across < 0.0 {
+            across = -across;
+            self.velocity.1 = -self.velocity.1;
+        } else if across > side {
+            across = 2.0 * side - across;
+            self.velocity.1 = -self.velocity.1;
+        }
+        self.ball = (along, across);
+
+        // The paddles sit on the first and last cells along the court
+        let end = (Self::LENGTH - 2) as f32;
+        if along <= 1.0 && self.velocity.0 < 0.0 {
+            if self.hit(0) {
+                along = 2.0 - along;
+            } else if along < 0.0 {
+                self.point(1);
+            }
+        } else if along >= end && self.velocity.0 > 0.0 {
+            if self.hit(1) {
+                along = 2.0 * end - along;
+            } else if along > end + 1.0 {
+                self.point(0);
+            }
+        }
+        self.ball.0 = along;
+    }
+
+    fn set<const S: usize>(buffer: &mut RenderBuffer<S, X, Y>, along: i32, across: i32, color: FixedColor) {
+        if X >= Y {
+            buffer.safe_set_pixel_signed(along, across, color);
+        } else {
+            buffer.safe_set_pixel_signed(across, along, color);
+        }
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Pong<X, Y> {
+    fn step(&mut self) {
+        match self.state {
+            PongState::Serving { steps: 0, to } => self.serve(to),
+            PongState::Serving { steps, to } => {
+                self.ball = Self::centre();
+                self.state = PongState::Serving { steps: steps - 1, to };
+            }
+            PongState::Playing => self.advance(),
+            PongState::Won { steps: 0, .. } => *self = Self::new(),
+            PongState::Won { player, steps } => self.state = PongState::Won { player, steps: steps - 1 },
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        if let PongState::Won { player, steps } = self.state {
+            // Flash the winner's half of the court
+            if (steps / FLASH_STEPS) & 1 == 0 {
+                let half = Self::LENGTH / 2;
+                let start = if player == 0 { 0 } else { Self::LENGTH - half };
+                for along in start..start + half {
+                    for across in 0..Self::WIDTH {
+                        Self::set(buffer, along, across, PONG_COLORS[player]);
+                    }
+                }
+            }
+            return;
+        }
+
+        for (player, &paddle) in self.paddles.iter().enumerate() {
+            let along = if player == 0 { 0 } else { Self::LENGTH - 1 };
+            for across in paddle..paddle + Self::PADDLE {
+                Self::set(buffer, along, across, PONG_COLORS[player]);
+            }
+        }
+
+        // Show the score while waiting to serve, counting out from the centre
+        if let PongState::Serving { .. } = self.state {
+            let centre = Self::LENGTH / 2;
+            for point in 0..self.scores[0] as i32 {
+                Self::set(buffer, centre - 2 - point * 2, 0, PONG_COLORS[0]);
+            }
+            for point in 0..self.scores[1] as i32 {
+                Self::set(buffer, centre + 1 + point * 2, 0, PONG_COLORS[1]);
+            }
+        }
+
+        let (along, across) = self.ball;
+        Self::set(buffer, libm::roundf(along) as i32, libm::roundf(across) as i32, FixedColor::WHITE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step<R: Render<{ 10 * 6 }, 10, 6>>(renderer: &mut R, steps: u32) {
+        for _ in 0..steps {
+            renderer.step();
+        }
+    }
+
+    #[test]
+    fn test_snake_eats_and_grows() {
+        let mut snake = Snake::<10, 6>::new();
+        snake.food = (7, 3);
+        step(&mut snake, SNAKE_STEPS_PER_MOVE * 2);
+        assert_eq!(snake.body.iter().copied().collect::<heapless::Vec<_, 4>>(), [(7, 3), (6, 3), (5, 3)]);
+        assert_eq!(snake.grow, SNAKE_GROWTH);
+        assert_ne!(snake.food, (7, 3));
+    }
+
+    #[test]
+    fn test_snake_cannot_reverse() {
+        let mut snake = Snake::<10, 6>::new();
+        snake.input(Input::Left);
+        step(&mut snake, SNAKE_STEPS_PER_MOVE);
+        assert_eq!(snake.heading, Direction::Right);
+
+        // Wraps around the edge instead of ending the game
+        snake.input(Input::Up);
+        step(&mut snake, SNAKE_STEPS_PER_MOVE * 4);
+        assert_eq!(snake.body.front(), Some(&(6, 5)));
+        assert_eq!(snake.state, SnakeState::Playing);
+    }
+
+    #[test]
+    fn test_snake_runs_into_itself() {
+        let mut snake = Snake::<10, 6>::new();
+        snake.grow = 5;
+        for input in [Input::Right, Input::Down, Input::Left, Input::Up] {
+            snake.input(input);
+            step(&mut snake, SNAKE_STEPS_PER_MOVE);
+        }
+        assert_eq!(snake.state, SnakeState::Over(GAME_OVER_STEPS));
+
+        snake.input(Input::Button);
+        assert_eq!(snake.state, SnakeState::Playing);
+        assert_eq!(snake.body.len(), 1);
+    }
+
+    #[test]
+    fn test_pong_paddles_and_points() {
+        let mut pong = Pong::<10, 6>::new();
+        for _ in 0..10 {
+            pong.input(0, Input::Up);
+            pong.input(1, Input::Down);
+        }
+        assert_eq!(pong.paddles, [0, 6 - Pong::<10, 6>::PADDLE]);
+
+        // A served ball misses the paddle at the top and scores for player 0
+        pong.input(1, Input::Button);
+        step(&mut pong, 1);
+        pong.velocity.1 = 0.0;
+        pong.ball.1 = 0.0;
+        step(&mut pong, 40);
+        assert_eq!(pong.scores, [1, 0]);
+        assert!(matches!(pong.state, PongState::Serving { to: 1, .. }));
+    }
+
+    #[test]
+    fn test_pong_paddle_returns_ball() {
+        let mut pong = Pong::<10, 6>::new();
+        pong.input(1, Input::Button);
+        step(&mut pong, 1);
+        pong.velocity.1 = 0.0;
+        pong.ball.1 = (pong.paddles[1] + 1) as f32;
+        step(&mut pong, 20);
+        assert!(pong.velocity.0 < 0.0);
+        assert_eq!(pong.scores, [0, 0]);
+    }
+}
@@ -0,0 +1,100 @@
+// embedded-graphics integration, so fonts, shapes and images from that
+// ecosystem can draw straight into a RenderBuffer.
+//
+// The buffer draws in Rgb888. Other colour types (Rgb565, Rgb555, BinaryColor...)
+// can be drawn through `DrawTargetExt::color_converted`.
+
+use embedded_graphics_core::draw_target::DrawTarget;
+use embedded_graphics_core::geometry::{Dimensions, OriginDimensions, Size};
+use embedded_graphics_core::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics_core::primitives::Rectangle;
+use embedded_graphics_core::Pixel;
+
+use crate::fixedcolor::FixedColor;
+use crate::RenderBuffer;
+
+impl From<Rgb888> for FixedColor {
+    fn from(color: Rgb888) -> Self {
+        FixedColor::from_rgb8(color.r(), color.g(), color.b())
+    }
+}
+
+impl From<FixedColor> for Rgb888 {
+    fn from(color: FixedColor) -> Self {
+        let (r, g, b) = color.as_rgb8();
+        Rgb888::new(r, g, b)
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> OriginDimensions for RenderBuffer<S, X, Y> {
+    fn size(&self) -> Size {
+        Size::new(X as u32, Y as u32)
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> DrawTarget for RenderBuffer<S, X, Y> {
+    type Color = Rgb888;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            // Negative coordinates are off the buffer, let safe_set_pixel clip the rest
+            if point.x >= 0 && point.y >= 0 {
+                self.safe_set_pixel(point.x as u32, point.y as u32, color.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        let color: FixedColor = color.into();
+        for y in area.rows() {
+            for x in area.columns() {
+                self.safe_set_pixel(x as u32, y as u32, color);
+            }
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.clear_to_color(color.into());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics_core::geometry::Point;
+
+    type Buffer = RenderBuffer<{ 4 * 3 }, 4, 3>;
+
+    #[test]
+    fn test_draw_iter_clips() {
+        let mut buffer = Buffer::new();
+        let pixels = [
+            Pixel(Point::new(1, 2), Rgb888::RED),
+            Pixel(Point::new(-1, 0), Rgb888::WHITE),
+            Pixel(Point::new(4, 0), Rgb888::WHITE),
+        ];
+        buffer.draw_iter(pixels).unwrap();
+
+        assert_eq!(buffer.get_pixel(1, 2).as_rgb8(), (255, 0, 0));
+        assert_eq!(buffer.buffer().iter().filter(|p| p.as_rgb8() != (0, 0, 0)).count(), 1);
+    }
+
+    #[test]
+    fn test_fill_solid() {
+        let mut buffer = Buffer::new();
+        let area = Rectangle::new(Point::new(2, 1), Size::new(5, 5));
+        buffer.fill_solid(&area, Rgb888::GREEN).unwrap();
+
+        assert_eq!(buffer.get_pixel(3, 2).as_rgb8(), (0, 255, 0));
+        assert_eq!(buffer.get_pixel(1, 1).as_rgb8(), (0, 0, 0));
+        assert_eq!(OriginDimensions::size(&buffer), Size::new(4, 3));
+    }
+}
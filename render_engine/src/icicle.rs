@@ -0,0 +1,243 @@
+// Effects built for icicle lights, where every drop is its own column with
+// its own state and timing.
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::fixedcolor::FixedColor;
+use crate::render::Render;
+use crate::renderbuffer::Blend;
+use crate::RenderBuffer;
+
+const ICE_COLOR: FixedColor = FixedColor::rgb(0.6, 0.8, 1.0);
+// Pixels per step per step
+const DRIP_GRAVITY: f32 = 0.015;
+// Steps to wait before a new droplet forms
+const DRIP_WAIT: core::ops::Range<u32> = 10..120;
+// How much a forming droplet swells each step
+const DRIP_SWELL: core::ops::Range<f32> = 0.01..0.04;
+// Tail length in pixels per pixel-per-step of speed
+const DRIP_TAIL: f32 = 4.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Drip {
+    Waiting(u32),
+    // Swelling at the top of the drop, until it reaches 1.0 and lets go
+    Forming { size: f32, rate: f32 },
+    Falling { y: f32, speed: f32 },
+}
+
+pub struct Icicle<const X: usize, const Y: usize> {
+    drips: [Drip; X],
+    rng: SmallRng,
+}
+
+impl<const X: usize, const Y: usize> Icicle<X, Y> {
+    pub fn new() -> Self {
+        let mut rng = SmallRng::seed_from_u64(0);
+        Self {
+            drips: core::array::from_fn(|_| Drip::Waiting(rng.gen_range(DRIP_WAIT))),
+            rng,
+        }
+    }
+
+    fn step_drip(drip: Drip, rng: &mut SmallRng) -> Drip {
+        match drip {
+            Drip::Waiting(0) => Drip::Forming {
+                size: 0.0,
+                rate: rng.gen_range(DRIP_SWELL),
+            },
+            Drip::Waiting(n) => Drip::Waiting(n - 1),
+            Drip::Forming { size, .. } if size >= 1.0 => Drip::Falling { y: 0.0, speed: 0.0 },
+            Drip::Forming { size, rate } => Drip::Forming { size: size + rate, rate },
+            // Wait until the end of the tail has left the drop
+            Drip::Falling { y, speed } if y - Self::tail(speed) >= Y as f32 => Drip::Waiting(rng.gen_range(DRIP_WAIT)),
+            Drip::Falling { y, speed } => Drip::Falling {
+                y: y + speed,
+                speed: speed + DRIP_GRAVITY,
+            },
+        }
+    }
+
+    fn tail(speed: f32) -> f32 {
+        1.0 + speed * DRIP_TAIL
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Icicle<X, Y> {
+    fn step(&mut self) {
+        for drip in self.drips.iter_mut() {
+            *drip = Self::step_drip(*drip, &mut self.rng);
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, blend: Blend) {
+        for (x, drip) in self.drips.iter().enumerate() {
+            let x = x as u32;
+            match *drip {
+                Drip::Waiting(_) => {}
+                Drip::Forming { size, .. } => {
+                    buffer.safe_set_max_rgb(x, 0, ICE_COLOR.scale(size.min(1.0)), blend);
+                }
+                Drip::Falling { y, speed } => {
+                    // A bright head with a tail that stretches as it speeds up
+                    let tail = Self::tail(speed);
+                    let top = libm::floorf(y - tail).max(0.0) as u32;
+                    let bottom = libm::floorf(y) as u32;
+                    for py in top..=bottom {
+                        let distance = y - py as f32;
+                        let brightness = 1.0 - distance / tail;
+                        if brightness > 0.0 {
+                            buffer.safe_set_max_rgb(x, py, ICE_COLOR.scale(brightness), blend);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// -----
+
+const MATRIX_HEAD: FixedColor = FixedColor::rgb(0.8, 1.0, 0.8);
+const MATRIX_TRAIL: FixedColor = FixedColor::rgb(0.0, 1.0, 0.2);
+const MATRIX_SPEED: core::ops::Range<f32> = 0.15..0.6;
+const MATRIX_LENGTH: core::ops::Range<f32> = 4.0..14.0;
+// Chance each step that a cell of code changes
+const MATRIX_FLICKER: f32 = 0.05;
+
+#[derive(Clone, Copy)]
+struct Stream {
+    head: f32,
+    speed: f32,
+    length: f32,
+}
+
+// Streams of falling code, each drop running at its own speed
+pub struct Matrix<const X: usize, const Y: usize> {
+    streams: [Stream; X],
+    // Per-cell brightness so the code looks like changing characters
+    code: [[u8; Y]; X],
+    rng: SmallRng,
+}
+
+impl<const X: usize, const Y: usize> Matrix<X, Y> {
+    pub fn new() -> Self {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let streams = core::array::from_fn(|_| {
+            let mut stream = Self::new_stream(&mut rng);
+            // Spread the first streams out so they don't all start together
+            stream.head = -rng.gen_range(0.0..Y as f32);
+            stream
+        });
+        Self {
+            streams,
+            code: core::array::from_fn(|_| core::array::from_fn(|_| rng.gen_range(96..=255))),
+            rng,
+        }
+    }
+
+    fn new_stream(rng: &mut SmallRng) -> Stream {
+        Stream {
+            head: 0.0,
+            speed: rng.gen_range(MATRIX_SPEED),
+            length: rng.gen_range(MATRIX_LENGTH),
+        }
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Matrix<X, Y> {
+    fn step(&mut self) {
+        for stream in self.streams.iter_mut() {
+            stream.head += stream.speed;
+            if stream.head - stream.length > Y as f32 {
+                *stream = Self::new_stream(&mut self.rng);
+            }
+        }
+        for cell in self.code.iter_mut().flatten() {
+            if self.rng.gen::<f32>() < MATRIX_FLICKER {
+                *cell = self.rng.gen_range(96..=255);
+            }
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        for (x, stream) in self.streams.iter().enumerate() {
+            let head = libm::floorf(stream.head) as i32;
+            for y in 0..Y as i32 {
+                let distance = (head - y) as f32;
+                if distance < 0.0 || distance > stream.length {
+                    continue;
+                }
+                let color = if distance < 1.0 {
+                    MATRIX_HEAD
+                } else {
+                    let code = self.code[x][y as usize] as f32 / 255.0;
+                    MATRIX_TRAIL.scale(code * (1.0 - distance / stream.length))
+                };
+                buffer.safe_set_pixel(x as u32, y as u32, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drip_lifecycle() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut drip = Drip::Waiting(0);
+        let mut formed = false;
+        let mut fell = false;
+        for _ in 0..1000 {
+            drip = Icicle::<1, 24>::step_drip(drip, &mut rng);
+            match drip {
+                Drip::Forming { .. } => formed = true,
+                Drip::Falling { .. } => fell = true,
+                Drip::Waiting(_) if fell => break,
+                Drip::Waiting(_) => {}
+            }
+        }
+        assert!(formed && fell);
+        assert!(matches!(drip, Drip::Waiting(_)));
+    }
+
+    #[test]
+    fn test_drip_accelerates() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut drip = Drip::Falling { y: 0.0, speed: 0.0 };
+        let mut last = (0.0, 0.0);
+        for _ in 0..10 {
+            drip = Icicle::<1, 24>::step_drip(drip, &mut rng);
+            let Drip::Falling { y, speed } = drip else { panic!() };
+            assert!(speed > last.1 && y >= last.0);
+            last = (y, speed);
+        }
+    }
+
+    #[test]
+    fn test_falling_drip_has_a_tail() {
+        type Buffer = RenderBuffer<{ 2 * 24 }, 2, 24>;
+        let mut icicle = Icicle::<2, 24>::new();
+        icicle.drips = [Drip::Waiting(5), Drip::Falling { y: 10.0, speed: 1.0 }];
+        let mut buffer = Buffer::new();
+        icicle.render(0.0, 0.0, &mut buffer, Blend::Dest);
+
+        assert!(buffer.buffer().iter().step_by(2).all(|p| p.as_rgb8() == (0, 0, 0)));
+        let head = buffer.get_pixel(1, 10).b;
+        let tail = buffer.get_pixel(1, 8).b;
+        assert!(head > tail && tail > 0.0);
+        assert_eq!(buffer.get_pixel(1, 11).as_rgb8(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_matrix_streams_restart() {
+        let mut matrix = Matrix::<3, 8>::new();
+        for _ in 0..1000 {
+            <Matrix<3, 8> as Render<24, 3, 8>>::step(&mut matrix);
+            assert!(matrix.streams.iter().all(|s| s.head - s.length <= 8.0 + MATRIX_SPEED.end));
+        }
+    }
+}
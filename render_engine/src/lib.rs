@@ -2,18 +2,47 @@
 
 use renderbuffer::Blend;
 pub use vec::{UVec2, Vec2};
-pub use render::RenderType;
+pub use audio::{AudioFeatures, MAX_BANDS};
+pub use beat::Beat;
+pub use clock::Date;
+pub use games::Input;
+pub use render::{marquee_text, FireSettings, Param, RenderType, MarqueeText, MARQUEE_CAPACITY};
 pub use renderbuffer::RenderBuffer;
+pub use sprite::{BlitOptions, Sprite, SpriteAnimation};
+pub use postprocess::{Axis, PostEffect};
+pub use transition::TransitionStyle;
+pub use viewport::{Transform, TransformAnimation, Wrap};
 //pub use shaders::Shader;
 //pub mod shaders;
+mod audio;
+mod automata;
+pub mod beat;
+pub mod clock;
+mod countdown;
+mod games;
+pub mod noise;
 mod render;
 mod renderbuffer;
 mod transition;
 pub mod fixedcolor;
+pub mod font;
+mod icicle;
+pub mod particles;
+mod postprocess;
+pub mod sprite;
+#[cfg(feature = "embedded-graphics")]
+mod graphics;
 mod vec;
+mod viewport;
 
+use beat::BeatClock;
+use clock::WallClock;
+use postprocess::PostChain;
 use transition::Transition;
 
+// Fastest effects can be run, in steps per frame
+pub const MAX_SPEED: f32 = 4.0;
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum Renderer {
     Basic(render::RenderType),
@@ -21,25 +50,54 @@ pub enum Renderer {
     None
 }
 
-pub struct RenderEngine<const S: usize, const X: usize, const Y: usize> {
+// Draws effects onto an X by Y output. Effects run at EX by EY, the output
+// size unless given, and are scaled to fit the output when the two differ,
+// e.g. to show an effect made for 50x24 on a 5x24 strip.
+pub struct RenderEngine<const S: usize, const X: usize, const Y: usize, const ES: usize = S, const EX: usize = X, const EY: usize = Y> {
     renderer: Renderer,
     transition: Option<Transition<f32>>,
-    render_engine: render::Renderers<S, X, Y>,
+    render_engine: render::Renderers<ES, EX, EY>,
+    post_effects: PostChain,
+    transform: Option<(Transform, TransformAnimation)>,
+    // The effect draws here, then is transformed or copied onto the output
+    // and post processed there. Decay fades what is left in it, so trails
+    // are of the effect and not of the last post processed frame.
+    effect_buffer: RenderBuffer<ES, EX, EY>,
+    // The incoming effect draws here during a transition, and the outgoing
+    // frame is mixed into it
+    transition_buffer: RenderBuffer<ES, EX, EY>,
+    // A transition away from frames sent straight to the output holds the
+    // last of them in effect_buffer, taken on the next render
+    hold_output: bool,
+    clock: WallClock,
+    beat: BeatClock,
+    speed: f32,
+    // Effect steps owed, so speeds below 1.0 skip frames and above take extra steps
+    steps: f32,
 }
 
-impl<const S: usize, const X: usize, const Y: usize> Default for RenderEngine<S, X, Y> {
+impl<const S: usize, const X: usize, const Y: usize, const ES: usize, const EX: usize, const EY: usize> Default for RenderEngine<S, X, Y, ES, EX, EY> {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<const S: usize, const X: usize, const Y: usize> RenderEngine<S, X, Y> {
+impl<const S: usize, const X: usize, const Y: usize, const ES: usize, const EX: usize, const EY: usize> RenderEngine<S, X, Y, ES, EX, EY> {
     pub fn new() -> Self {
         Self {
             renderer: Renderer::None,
             transition: None,
 
             render_engine: render::Renderers::new(),
+            post_effects: PostChain::new(),
+            transform: None,
+            effect_buffer: RenderBuffer::new(),
+            transition_buffer: RenderBuffer::new(),
+            hold_output: false,
+            clock: WallClock::new(),
+            beat: BeatClock::new(),
+            speed: 1.0,
+            steps: 0.0,
         }
     }
 
@@ -51,53 +109,322 @@ impl<const S: usize, const X: usize, const Y: usize> RenderEngine<S, X, Y> {
         self.renderer
     }
 
+    pub fn set_text(&mut self, text: &str) {
+        self.render_engine.set_marquee_text(text);
+    }
+
+    pub fn set_fire_settings(&mut self, settings: FireSettings) {
+        self.render_engine.set_fire_settings(settings);
+    }
+
+    pub fn set_param(&mut self, param: Param) {
+        match param {
+            Param::Speed(speed) => self.speed = speed.clamp(0.0, MAX_SPEED),
+            param => self.render_engine.set_param(param),
+        }
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    // The latest frame of audio features from the host
+    pub fn set_audio(&mut self, features: &AudioFeatures) {
+        self.render_engine.set_audio(features);
+    }
+
+    // None stops the beat clock
+    pub fn set_bpm(&mut self, bpm: Option<f32>) {
+        self.beat.set_bpm(bpm);
+    }
+
+    pub fn tap_tempo(&mut self) {
+        self.beat.tap();
+    }
+
+    // Make now the start of a beat, without changing the tempo
+    pub fn sync_beat(&mut self) {
+        self.beat.sync();
+    }
+
+    // The beat as of the last frame, None while the beat clock is stopped
+    pub fn beat(&self) -> Option<Beat> {
+        self.beat.beat()
+    }
+
+    // Controller input for the games, ignored by everything else. During a
+    // transition it goes to the incoming effect, the one being switched to.
+    pub fn input(&mut self, player: u8, input: Input) {
+        let renderer = self.transition.as_ref().map_or(self.renderer, |transition| transition.renderer);
+        if let Renderer::Basic(r) = renderer {
+            self.render_engine.input(r, player, input);
+        }
+    }
+
+    // Local seconds since 1970, advanced by dt on every render
+    pub fn set_time(&mut self, seconds: u64) {
+        self.clock.set(seconds);
+    }
+
+    pub fn get_time(&self) -> Option<u64> {
+        self.clock.now()
+    }
+
+    // Keep the clock running on frames that are not rendered
+    pub fn step_clock(&mut self, dt: f32) {
+        self.clock.step(dt);
+    }
+
+    // None counts down to the next Christmas
+    pub fn set_countdown_target(&mut self, target: Option<Date>) {
+        self.render_engine.set_countdown_target(target);
+    }
+
+    pub fn post_effects(&self) -> &[PostEffect] {
+        self.post_effects.effects()
+    }
+
+    // Append an effect to the end of the chain, handing it back if the chain is full
+    pub fn push_post_effect(&mut self, effect: PostEffect) -> Result<(), PostEffect> {
+        self.post_effects.push(effect)
+    }
+
+    pub fn clear_post_effects(&mut self) {
+        self.post_effects.clear();
+    }
+
+    pub fn get_transform(&self) -> Option<Transform> {
+        self.transform.map(|(transform, _)| transform)
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.set_animated_transform(transform, TransformAnimation::default());
+    }
+
+    pub fn set_animated_transform(&mut self, transform: Transform, animation: TransformAnimation) {
+        self.transform = Some((transform, animation));
+    }
+
+    pub fn clear_transform(&mut self) {
+        self.transform = None;
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_some()
+    }
+
     pub fn tx_progress(&self) -> f32 {
         self.transition.as_ref().map(|t| t.progress()).unwrap_or(0.0)
     }
 
     pub fn set_transition_to_renderer(&mut self, renderer: Renderer, duration: f32) {
-        self.transition = Some(Transition::new(renderer, duration));
+        self.set_transition_with_style(renderer, duration, TransitionStyle::default());
+    }
+
+    pub fn set_transition_with_style(&mut self, renderer: Renderer, duration: f32, style: TransitionStyle) {
+        if self.renderer == Renderer::None && self.transition.is_none() {
+            self.hold_output = true;
+        }
+        // Trails left by Decay belong to the last transition
+        self.transition_buffer.clear();
+        self.transition = Some(Transition::new(renderer, duration, style));
+    }
+
+    // Clear the buffer, or fade it when the previous frame should leave
+    // trails, and draw a frame of the effect into it
+    fn draw(renderers: &mut render::Renderers<ES, EX, EY>, post_effects: &PostChain, renderer: Renderer, steps: u32, t: f32, dt: f32, buffer: &mut RenderBuffer<ES, EX, EY>) {
+        match post_effects.decay() {
+            Some(keep) => buffer.fade(keep),
+            None => buffer.clear(),
+        }
+        if let Renderer::Basic(r) = renderer {
+            for _ in 0..steps {
+                renderers.step(r);
+            }
+            renderers.render(r, t, dt, buffer, Blend::Dest);
+        }
     }
 
     pub fn render(&mut self, t: f32, dt: f32, b: &mut RenderBuffer<S, X, Y>) {
+        self.clock.step(dt);
+        self.render_engine.set_wall_clock(self.clock.now());
+        self.beat.step(dt);
+        self.render_engine.set_beat(self.beat.beat());
+
         if let Some(transition) = &mut self.transition {
             transition.step(dt);
             if transition.is_done() {
+                // Carry on from the last mixed frame, trails and all
+                if mixes(self.renderer, transition) {
+                    core::mem::swap(&mut self.effect_buffer, &mut self.transition_buffer);
+                }
                 self.renderer = transition.renderer;
                 self.transition = None;
             }
         }
+        if core::mem::take(&mut self.hold_output) && self.renderer == Renderer::None {
+            copy(b, &mut self.effect_buffer);
+        }
+
+        let incoming = self.transition.as_ref().filter(|transition| mixes(self.renderer, transition));
+        let incoming = incoming.map(|transition| (transition.renderer, transition.style, transition.progress()));
+
+        // Nothing is drawn over frames sent straight to the buffer
+        if self.renderer == Renderer::None && incoming.is_none() {
+            return;
+        }
 
-        match self.renderer {
-            Renderer::Basic(r) => {
-                // Only clear the buffer if there is something to render
-                b.clear();
-                self.render_engine.step(r);
-                self.render_engine.render(r, t, dt, b, Blend::Dest);
+        // Effects run at their own speed, the clocks above keep real time
+        self.steps = (self.steps + self.speed).min(MAX_SPEED);
+        let steps = libm::floorf(self.steps);
+        self.steps -= steps;
+        let dt = dt * self.speed;
+
+        // Without an effect the held frame stays as it is while it fades out
+        if self.renderer != Renderer::None {
+            Self::draw(&mut self.render_engine, &self.post_effects, self.renderer, steps as u32, t, dt, &mut self.effect_buffer);
+        }
+        let frame = match incoming {
+            Some((renderer, style, progress)) => {
+                Self::draw(&mut self.render_engine, &self.post_effects, renderer, steps as u32, t, dt, &mut self.transition_buffer);
+                style.mix(progress, &self.effect_buffer, &mut self.transition_buffer);
+                &self.transition_buffer
+            }
+            None => &self.effect_buffer,
+        };
+
+        match &mut self.transform {
+            Some((transform, animation)) => {
+                transform.apply(frame, b);
+                animation.step(transform);
             }
-            Renderer::None => {}
+            None => copy(frame, b),
         }
+        self.post_effects.apply(b);
+    }
+}
 
-        // self.front_buffer.clear();
-        // if let Some(transition) = &mut self.transition {
-        //     match transition.renderer {
-        //         Renderer::Basic(r) => {
-        //             self.render_engine.step(r);
-        //             self.render_engine.render(r, t, dt, &mut self.front_buffer);
-        //         }
-        //         Renderer::None => {}
-        //     }
-        // }
+// The incoming effect is drawn alongside the outgoing one, unless they are
+// the same effect and would step each other twice a frame
+fn mixes(outgoing: Renderer, transition: &Transition<f32>) -> bool {
+    transition.style != TransitionStyle::Cut && !same_effect(outgoing, transition.renderer)
+}
+
+// A straight copy between buffers of the same size, scaled to fit otherwise
+fn copy<const S1: usize, const X1: usize, const Y1: usize, const S2: usize, const X2: usize, const Y2: usize>(
+    src: &RenderBuffer<S1, X1, Y1>,
+    dst: &mut RenderBuffer<S2, X2, Y2>,
+) {
+    if (X1, Y1) == (X2, Y2) {
+        dst.buffer_mut().copy_from_slice(src.buffer());
+    } else {
+        Transform::IDENTITY.scaled(X2 as f32 / X1 as f32, Y2 as f32 / Y1 as f32).apply(src, dst);
+    }
+}
 
-        // let progress = self.transition.as_ref().map(|t| t.progress()).unwrap_or(Fixed::ZERO);
+fn same_effect(a: Renderer, b: Renderer) -> bool {
+    match (a, b) {
+        (Renderer::Basic(a), Renderer::Basic(b)) => core::mem::discriminant(&a) == core::mem::discriminant(&b),
+        _ => false,
+    }
+}
 
-        // self.back_buffer.buffer().iter().zip(self.front_buffer.buffer().iter()).enumerate().for_each(|(index, (back, front))| {
-        //     let x = Fixed::ONE - progress;
-        //     let y = progress;
-        //     let new_color = back.scale(x).saturating_add(front.scale(y));
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        //     b.safe_set_pixel((index % X) as u32, (index / X) as u32, new_color);
-        // });
+    type Engine = RenderEngine<{ 16 * 8 }, 16, 8>;
+    type Buffer = RenderBuffer<{ 16 * 8 }, 16, 8>;
 
+    #[test]
+    fn test_decay_keeps_post_effects_stable() {
+        let mut engine = Engine::new();
+        engine.set_renderer(Renderer::Basic(RenderType::Marquee));
+        engine.set_text("HI");
+        engine.set_param(Param::Speed(0.0));
+        engine.push_post_effect(PostEffect::Decay(0.5)).unwrap();
+        engine.push_post_effect(PostEffect::Invert).unwrap();
+
+        let mut first = Buffer::new();
+        engine.render(0.0, 0.02, &mut first);
+        let mut second = first.clone();
+        engine.render(0.02, 0.02, &mut second);
+
+        // Inverted once per frame, not once more for every frame of trails
+        assert!(first.buffer().iter().any(|p| p.as_rgb8() == (255, 255, 255)));
+        assert!(first.buffer().iter().zip(second.buffer()).all(|(a, b)| a.as_rgb8() == b.as_rgb8()));
+    }
+
+    #[test]
+    fn test_crossfade_from_streamed_frames() {
+        let mut engine = Engine::new();
+        engine.set_renderer(Renderer::Basic(RenderType::Marquee));
+        engine.set_text("");
+        engine.set_renderer(Renderer::None);
+
+        // A frame from the host, then a fade to an effect that draws nothing
+        let mut b = Buffer::new();
+        b.clear_to_color(fixedcolor::FixedColor::from_rgb8(200, 0, 0));
+        engine.set_transition_with_style(Renderer::Basic(RenderType::Marquee), 1.0, TransitionStyle::Crossfade);
+
+        // Faded from the held frame each time, not from black or the last mix
+        for (dt, red) in [(0.5, 100), (0.25, 50)] {
+            engine.render(0.0, dt, &mut b);
+            let (r, _, _) = b.get_pixel(3, 3).as_rgb8();
+            assert!(r.abs_diff(red) <= 1, "{} {}", r, red);
+        }
+    }
+
+    #[test]
+    fn test_input_goes_to_the_incoming_game() {
+        let frame = |input: Option<Input>| {
+            let mut engine = Engine::new();
+            engine.set_renderer(Renderer::Basic(RenderType::Rainbow));
+            engine.set_transition_to_renderer(Renderer::Basic(RenderType::Snake), 10.0);
+            if let Some(input) = input {
+                engine.input(0, input);
+            }
+            let mut b = Buffer::new();
+            for _ in 0..4 {
+                engine.render(0.0, 0.02, &mut b);
+            }
+            b
+        };
+        let (steered, straight) = (frame(Some(Input::Up)), frame(None));
+        assert!(steered.buffer().iter().zip(straight.buffer()).any(|(a, b)| a.as_rgb8() != b.as_rgb8()));
     }
-}
\ No newline at end of file
+
+    type Strip = RenderEngine<{ 5 * 24 }, 5, 24, { 50 * 24 }, 50, 24>;
+
+    #[test]
+    fn test_effect_scaled_to_fit_smaller_output() {
+        assert_strip_shows_effect(Strip::new());
+    }
+
+    #[test]
+    fn test_effect_transformed_onto_smaller_output() {
+        let mut strip = Strip::new();
+        strip.set_transform(Transform::IDENTITY.scaled(0.1, 1.0));
+        assert_strip_shows_effect(strip);
+    }
+
+    fn assert_strip_shows_effect(mut strip: Strip) {
+        let mut full = RenderEngine::<{ 50 * 24 }, 50, 24>::new();
+        full.set_renderer(Renderer::Basic(RenderType::Rainbow));
+        strip.set_renderer(Renderer::Basic(RenderType::Rainbow));
+
+        let mut reference = RenderBuffer::<{ 50 * 24 }, 50, 24>::new();
+        full.render(0.0, 0.02, &mut reference);
+        let mut output = RenderBuffer::<{ 5 * 24 }, 5, 24>::new();
+        strip.render(0.0, 0.02, &mut output);
+
+        // Each strip pixel shows the middle of a tenth of the effect
+        for x in 0..5 {
+            let (r, g, b) = output.get_pixel(x, 12).as_rgb8();
+            let (er, eg, eb) = reference.get_pixel(x * 10 + 5, 12).as_rgb8();
+            for (channel, expected) in [(r, er), (g, eg), (b, eb)] {
+                assert!(channel.abs_diff(expected) <= 4, "{} {} {}", x, channel, expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,339 @@
+// Deterministic, allocation-free coherent noise for organic looking effects.
+//
+// All functions return values in roughly -1..1. Lattice points are hashed
+// from their integer coordinates and a seed, so there are no permutation
+// tables to keep in RAM.
+
+use libm::floorf;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NoiseKind {
+    Value,
+    Perlin,
+    Simplex,
+}
+
+// Octaves summed by `Noise::fractal2` and `Noise::fractal3`. Each octave is
+// `lacunarity` times the frequency and `gain` times the amplitude of the last.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Fractal {
+    pub kind: NoiseKind,
+    pub octaves: u32,
+    pub lacunarity: f32,
+    pub gain: f32,
+}
+
+impl Fractal {
+    pub const fn new(kind: NoiseKind, octaves: u32) -> Self {
+        Self {
+            kind,
+            octaves,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Noise {
+    seed: u32,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+fn hash(x: i32, y: i32, z: i32, seed: u32) -> u32 {
+    let mut h = seed
+        ^ (x as u32).wrapping_mul(0x8da6b343)
+        ^ (y as u32).wrapping_mul(0xd8163841)
+        ^ (z as u32).wrapping_mul(0xcb1ab31f);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b3c6d);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297a2d39);
+    h ^ (h >> 15)
+}
+
+// Quintic ease, so the noise has a continuous second derivative
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+// One of 8 directions around the unit circle
+fn grad2(h: u32, x: f32, y: f32) -> f32 {
+    match h & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+// Perlin's 12 edge directions of a cube
+fn grad3(h: u32, x: f32, y: f32, z: f32) -> f32 {
+    match h % 12 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+impl Noise {
+    pub const fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    fn lattice(&self, x: i32, y: i32, z: i32) -> f32 {
+        (hash(x, y, z, self.seed) & 0xffff) as f32 / 32767.5 - 1.0
+    }
+
+    pub fn value2(&self, x: f32, y: f32) -> f32 {
+        self.value3(x, y, 0.0)
+    }
+
+    pub fn value3(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (x0, y0, z0) = (floorf(x), floorf(y), floorf(z));
+        let (u, v, w) = (fade(x - x0), fade(y - y0), fade(z - z0));
+        let (x0, y0, z0) = (x0 as i32, y0 as i32, z0 as i32);
+
+        let corner = |dx: i32, dy: i32, dz: i32| self.lattice(x0 + dx, y0 + dy, z0 + dz);
+        let near = lerp(lerp(corner(0, 0, 0), corner(1, 0, 0), u), lerp(corner(0, 1, 0), corner(1, 1, 0), u), v);
+        let far = lerp(lerp(corner(0, 0, 1), corner(1, 0, 1), u), lerp(corner(0, 1, 1), corner(1, 1, 1), u), v);
+        lerp(near, far, w)
+    }
+
+    pub fn perlin2(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (floorf(x), floorf(y));
+        let (fx, fy) = (x - x0, y - y0);
+        let (u, v) = (fade(fx), fade(fy));
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let corner = |dx: i32, dy: i32| grad2(hash(x0 + dx, y0 + dy, 0, self.seed), fx - dx as f32, fy - dy as f32);
+        let n = lerp(lerp(corner(0, 0), corner(1, 0), u), lerp(corner(0, 1), corner(1, 1), u), v);
+        // The diagonal gradients reach sqrt(2) * 0.5
+        (n * core::f32::consts::FRAC_1_SQRT_2 * 2.0).clamp(-1.0, 1.0)
+    }
+
+    pub fn perlin3(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (x0, y0, z0) = (floorf(x), floorf(y), floorf(z));
+        let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+        let (x0, y0, z0) = (x0 as i32, y0 as i32, z0 as i32);
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            let h = hash(x0 + dx, y0 + dy, z0 + dz, self.seed);
+            grad3(h, fx - dx as f32, fy - dy as f32, fz - dz as f32)
+        };
+        let near = lerp(lerp(corner(0, 0, 0), corner(1, 0, 0), u), lerp(corner(0, 1, 0), corner(1, 1, 0), u), v);
+        let far = lerp(lerp(corner(0, 0, 1), corner(1, 0, 1), u), lerp(corner(0, 1, 1), corner(1, 1, 1), u), v);
+        lerp(near, far, w).clamp(-1.0, 1.0)
+    }
+
+    pub fn simplex2(&self, x: f32, y: f32) -> f32 {
+        const F2: f32 = 0.366_025_42; // (sqrt(3) - 1) / 2
+        const G2: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+
+        // Skew onto the simplex grid to find which triangle we are in
+        let s = (x + y) * F2;
+        let (i, j) = (floorf(x + s), floorf(y + s));
+        let t = (i + j) * G2;
+        let (x0, y0) = (x - (i - t), y - (j - t));
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let (i, j) = (i as i32, j as i32);
+
+        let corners = [
+            (x0, y0, 0, 0),
+            (x0 - i1 as f32 + G2, y0 - j1 as f32 + G2, i1, j1),
+            (x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2, 1, 1),
+        ];
+        let n: f32 = corners
+            .iter()
+            .map(|&(cx, cy, di, dj)| {
+                let t = 0.5 - cx * cx - cy * cy;
+                if t < 0.0 {
+                    0.0
+                } else {
+                    let t2 = t * t;
+                    t2 * t2 * grad2(hash(i + di, j + dj, 0, self.seed), cx, cy)
+                }
+            })
+            .sum();
+        (70.0 * n).clamp(-1.0, 1.0)
+    }
+
+    pub fn simplex3(&self, x: f32, y: f32, z: f32) -> f32 {
+        const F3: f32 = 1.0 / 3.0;
+        const G3: f32 = 1.0 / 6.0;
+
+        let s = (x + y + z) * F3;
+        let (i, j, k) = (floorf(x + s), floorf(y + s), floorf(z + s));
+        let t = (i + j + k) * G3;
+        let (x0, y0, z0) = (x - (i - t), y - (j - t), z - (k - t));
+
+        // Which of the six tetrahedra we are in
+        let ((i1, j1, k1), (i2, j2, k2)) = if x0 >= y0 {
+            if y0 >= z0 {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x0 >= z0 {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if y0 < z0 {
+            ((0, 0, 1), (0, 1, 1))
+        } else if x0 < z0 {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+        let (i, j, k) = (i as i32, j as i32, k as i32);
+
+        let offset = |di: i32, dj: i32, dk: i32, g: f32| (x0 - di as f32 + g, y0 - dj as f32 + g, z0 - dk as f32 + g, di, dj, dk);
+        let corners = [
+            offset(0, 0, 0, 0.0),
+            offset(i1, j1, k1, G3),
+            offset(i2, j2, k2, 2.0 * G3),
+            offset(1, 1, 1, 3.0 * G3),
+        ];
+        let n: f32 = corners
+            .iter()
+            .map(|&(cx, cy, cz, di, dj, dk)| {
+                let t = 0.6 - cx * cx - cy * cy - cz * cz;
+                if t < 0.0 {
+                    0.0
+                } else {
+                    let t2 = t * t;
+                    t2 * t2 * grad3(hash(i + di, j + dj, k + dk, self.seed), cx, cy, cz)
+                }
+            })
+            .sum();
+        (32.0 * n).clamp(-1.0, 1.0)
+    }
+
+    pub fn sample2(&self, kind: NoiseKind, x: f32, y: f32) -> f32 {
+        match kind {
+            NoiseKind::Value => self.value2(x, y),
+            NoiseKind::Perlin => self.perlin2(x, y),
+            NoiseKind::Simplex => self.simplex2(x, y),
+        }
+    }
+
+    pub fn sample3(&self, kind: NoiseKind, x: f32, y: f32, z: f32) -> f32 {
+        match kind {
+            NoiseKind::Value => self.value3(x, y, z),
+            NoiseKind::Perlin => self.perlin3(x, y, z),
+            NoiseKind::Simplex => self.simplex3(x, y, z),
+        }
+    }
+
+    // Fractal sum of octaves, normalised back to -1..1
+    pub fn fractal2(&self, fractal: &Fractal, x: f32, y: f32) -> f32 {
+        self.fractal(fractal, |noise, f| noise.sample2(fractal.kind, x * f, y * f))
+    }
+
+    pub fn fractal3(&self, fractal: &Fractal, x: f32, y: f32, z: f32) -> f32 {
+        self.fractal(fractal, |noise, f| noise.sample3(fractal.kind, x * f, y * f, z * f))
+    }
+
+    fn fractal(&self, fractal: &Fractal, sample: impl Fn(Noise, f32) -> f32) -> f32 {
+        let mut sum = 0.0;
+        let mut total = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        for octave in 0..fractal.octaves.max(1) {
+            // A different seed per octave stops the lattices lining up
+            sum += sample(Self::new(self.seed.wrapping_add(octave)), frequency) * amplitude;
+            total += amplitude;
+            frequency *= fractal.lacunarity;
+            amplitude *= fractal.gain;
+        }
+        sum / total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [NoiseKind; 3] = [NoiseKind::Value, NoiseKind::Perlin, NoiseKind::Simplex];
+
+    fn points() -> impl Iterator<Item = (f32, f32, f32)> {
+        (0..500).map(|i| {
+            let i = i as f32;
+            (i * 0.137 - 20.0, i * 0.291 - 50.0, i * 0.073)
+        })
+    }
+
+    #[test]
+    fn test_deterministic_and_seeded() {
+        let a = Noise::new(1);
+        let b = Noise::new(2);
+        for kind in KINDS {
+            assert_eq!(a.sample3(kind, 1.3, 2.7, 0.4), Noise::new(1).sample3(kind, 1.3, 2.7, 0.4));
+            assert_ne!(a.sample2(kind, 1.3, 2.7), b.sample2(kind, 1.3, 2.7));
+        }
+    }
+
+    #[test]
+    fn test_range() {
+        let noise = Noise::new(7);
+        for kind in KINDS {
+            for (x, y, z) in points() {
+                assert!((-1.0..=1.0).contains(&noise.sample2(kind, x, y)));
+                assert!((-1.0..=1.0).contains(&noise.sample3(kind, x, y, z)));
+                assert!((-1.0..=1.0).contains(&noise.fractal3(&Fractal::new(kind, 4), x, y, z)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_continuous() {
+        let noise = Noise::new(3);
+        for kind in KINDS {
+            for (x, y, z) in points() {
+                let d = noise.sample3(kind, x, y, z) - noise.sample3(kind, x + 0.001, y, z);
+                assert!(d.abs() < 0.05, "{:?} jumped by {} at {},{},{}", kind, d, x, y, z);
+            }
+        }
+    }
+
+    #[test]
+    fn test_gradient_noise_zero_on_lattice() {
+        let noise = Noise::new(11);
+        assert_eq!(noise.perlin2(3.0, -4.0), 0.0);
+        assert_eq!(noise.perlin3(3.0, -4.0, 5.0), 0.0);
+        assert_eq!(noise.simplex2(0.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn test_not_flat() {
+        let noise = Noise::new(5);
+        for kind in KINDS {
+            let (min, max) = points().fold((1.0f32, -1.0f32), |(min, max), (x, y, z)| {
+                let n = noise.sample3(kind, x, y, z);
+                (min.min(n), max.max(n))
+            });
+            assert!(max - min > 0.5, "{:?} only spans {}..{}", kind, min, max);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+// A fixed-capacity particle pool for effects made of many short-lived points.
+//
+// Positions are in pixels and velocities in pixels per step. Effects step the
+// pool once per frame, so lifetimes are counted in frames too.
+
+use core::ops::Range;
+
+use rand::rngs::SmallRng;
+use rand::Rng;
+
+use crate::fixedcolor::FixedColor;
+use crate::renderbuffer::{blend_merge, Blend};
+use crate::RenderBuffer;
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Particle {
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+    pub age: f32,
+    pub lifetime: f32,
+    pub color: FixedColor,
+    alive: bool,
+}
+
+impl Particle {
+    pub fn new(x: f32, y: f32, vx: f32, vy: f32, lifetime: f32, color: FixedColor) -> Self {
+        Self {
+            x,
+            y,
+            vx,
+            vy,
+            age: 0.0,
+            lifetime,
+            color,
+            alive: true,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    pub fn kill(&mut self) {
+        self.alive = false;
+    }
+
+    // How far through its life the particle is, from 0 to 1
+    pub fn life(&self) -> f32 {
+        if self.lifetime <= 0.0 {
+            1.0
+        } else {
+            (self.age / self.lifetime).min(1.0)
+        }
+    }
+}
+
+// Colour stops over the life of a particle, multiplied into its own colour
+#[derive(Clone, Copy)]
+pub struct ColorCurve<'a> {
+    stops: &'a [(f32, FixedColor)],
+}
+
+impl<'a> ColorCurve<'a> {
+    // Stops must be sorted by life
+    pub const fn new(stops: &'a [(f32, FixedColor)]) -> Self {
+        Self { stops }
+    }
+
+    pub fn at(&self, life: f32) -> FixedColor {
+        let Some(first) = self.stops.first() else {
+            return FixedColor::WHITE;
+        };
+        if life <= first.0 {
+            return first.1;
+        }
+        for pair in self.stops.windows(2) {
+            let ((l0, c0), (l1, c1)) = (pair[0], pair[1]);
+            if life <= l1 {
+                let phase = if l1 > l0 { (life - l0) / (l1 - l0) } else { 1.0 };
+                return blend_merge(c0, c1, phase);
+            }
+        }
+        self.stops[self.stops.len() - 1].1
+    }
+}
+
+// Full brightness fading out to nothing
+pub const FADE_OUT: ColorCurve<'static> = ColorCurve::new(&[(0.0, FixedColor::WHITE), (1.0, FixedColor::BLACK)]);
+
+pub struct ParticlePool<const N: usize> {
+    particles: [Particle; N],
+    // Added to the vertical velocity every step, positive is down the drops
+    pub gravity: f32,
+    // Fraction of velocity lost every step
+    pub drag: f32,
+}
+
+impl<const N: usize> Default for ParticlePool<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> ParticlePool<N> {
+    pub fn new() -> Self {
+        Self {
+            particles: [Particle::default(); N],
+            gravity: 0.0,
+            drag: 0.0,
+        }
+    }
+
+    pub fn with_physics(gravity: f32, drag: f32) -> Self {
+        Self {
+            gravity,
+            drag,
+            ..Self::new()
+        }
+    }
+
+    // Returns false if the pool is full and the particle was dropped
+    pub fn spawn(&mut self, particle: Particle) -> bool {
+        match self.particles.iter_mut().find(|p| !p.alive) {
+            Some(slot) => {
+                *slot = Particle { alive: true, ..particle };
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn alive(&self) -> usize {
+        self.particles.iter().filter(|p| p.alive).count()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Particle> {
+        self.particles.iter().filter(|p| p.alive)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Particle> {
+        self.particles.iter_mut().filter(|p| p.alive)
+    }
+
+    pub fn clear(&mut self) {
+        for p in self.particles.iter_mut() {
+            p.alive = false;
+        }
+    }
+
+    pub fn step(&mut self) {
+        let keep = 1.0 - self.drag;
+        let gravity = self.gravity;
+        for p in self.iter_mut() {
+            p.vy += gravity;
+            p.vx *= keep;
+            p.vy *= keep;
+            p.x += p.vx;
+            p.y += p.vy;
+            p.age += 1.0;
+            if p.age >= p.lifetime {
+                p.alive = false;
+            }
+        }
+    }
+
+    pub fn render<const S: usize, const X: usize, const Y: usize>(&self, buffer: &mut RenderBuffer<S, X, Y>, curve: &ColorCurve) {
+        for p in self.iter() {
+            let x = libm::floorf(p.x);
+            let y = libm::floorf(p.y);
+            if x >= 0.0 && y >= 0.0 {
+                let color = p.color.multiply(curve.at(p.life()));
+                buffer.safe_set_max_rgb(x as u32, y as u32, color, Blend::Dest);
+            }
+        }
+    }
+}
+
+// Spawns particles from a point with a spread of directions and speeds
+#[derive(Clone)]
+pub struct Emitter {
+    pub x: f32,
+    pub y: f32,
+    // Direction in turns, 0 is up the drops and 0.25 is to the right
+    pub angle: Range<f32>,
+    pub speed: Range<f32>,
+    pub lifetime: Range<f32>,
+    pub color: FixedColor,
+}
+
+impl Emitter {
+    fn sample(rng: &mut SmallRng, range: &Range<f32>) -> f32 {
+        if range.start < range.end {
+            rng.gen_range(range.clone())
+        } else {
+            range.start
+        }
+    }
+
+    pub fn emit<const N: usize>(&self, pool: &mut ParticlePool<N>, rng: &mut SmallRng) -> bool {
+        let angle = Self::sample(rng, &self.angle) * 2.0 * core::f32::consts::PI;
+        let speed = Self::sample(rng, &self.speed);
+        let lifetime = Self::sample(rng, &self.lifetime);
+        let vx = libm::sinf(angle) * speed;
+        let vy = -libm::cosf(angle) * speed;
+        pool.spawn(Particle::new(self.x, self.y, vx, vy, lifetime, self.color))
+    }
+
+    // Emit up to `count` particles, stopping early if the pool fills up
+    pub fn burst<const N: usize>(&self, pool: &mut ParticlePool<N>, rng: &mut SmallRng, count: usize) {
+        for _ in 0..count {
+            if !self.emit(pool, rng) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_pool_capacity_and_lifetime() {
+        let mut pool = ParticlePool::<2>::new();
+        assert!(pool.spawn(Particle::new(0.0, 0.0, 0.0, 0.0, 1.0, FixedColor::WHITE)));
+        assert!(pool.spawn(Particle::new(0.0, 0.0, 0.0, 0.0, 3.0, FixedColor::WHITE)));
+        assert!(!pool.spawn(Particle::new(0.0, 0.0, 0.0, 0.0, 3.0, FixedColor::WHITE)));
+
+        pool.step();
+        assert_eq!(pool.alive(), 1);
+        assert!(pool.spawn(Particle::new(0.0, 0.0, 0.0, 0.0, 3.0, FixedColor::WHITE)));
+    }
+
+    #[test]
+    fn test_gravity_and_drag() {
+        let mut pool = ParticlePool::<1>::with_physics(1.0, 0.5);
+        pool.spawn(Particle::new(0.0, 0.0, 4.0, 0.0, 10.0, FixedColor::WHITE));
+        pool.step();
+
+        let p = pool.iter().next().unwrap();
+        assert_eq!((p.x, p.y), (2.0, 0.5));
+    }
+
+    #[test]
+    fn test_color_curve() {
+        let curve = ColorCurve::new(&[(0.0, FixedColor::WHITE), (0.5, FixedColor::RED), (1.0, FixedColor::BLACK)]);
+        assert_eq!(curve.at(0.0).as_rgb8(), (255, 255, 255));
+        assert_eq!(curve.at(0.5).as_rgb8(), (255, 0, 0));
+        assert_eq!(curve.at(0.75).as_rgb8(), (127, 0, 0));
+        assert_eq!(curve.at(2.0).as_rgb8(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_emitter_burst() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut pool = ParticlePool::<8>::new();
+        let emitter = Emitter {
+            x: 2.0,
+            y: 2.0,
+            angle: 0.0..1.0,
+            speed: 1.0..1.0,
+            lifetime: 5.0..5.0,
+            color: FixedColor::WHITE,
+        };
+        emitter.burst(&mut pool, &mut rng, 20);
+        assert_eq!(pool.alive(), 8);
+
+        pool.step();
+        for p in pool.iter() {
+            let distance = libm::sqrtf((p.x - 2.0) * (p.x - 2.0) + (p.y - 2.0) * (p.y - 2.0));
+            assert!((distance - 1.0).abs() < 0.001);
+        }
+    }
+}
@@ -0,0 +1,238 @@
+// Post-effects applied to the RenderBuffer after a renderer has drawn into it.
+
+use crate::fixedcolor::FixedColor;
+use crate::RenderBuffer;
+
+pub const MAX_POST_EFFECTS: usize = 6;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Axis {
+    // Left half reflected onto the right
+    Horizontal,
+    // Top half reflected onto the bottom
+    Vertical,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PostEffect {
+    // Keep this fraction of the previous frame instead of clearing, leaving trails
+    Decay(f32),
+    BoxBlur,
+    GaussianBlur,
+    Mirror(Axis),
+    Kaleidoscope,
+    Invert,
+    // Rotate every hue by a fraction of a full turn
+    HueRotate(f32),
+}
+
+#[derive(Default)]
+pub struct PostChain {
+    effects: heapless::Vec<PostEffect, MAX_POST_EFFECTS>,
+}
+
+impl PostChain {
+    pub fn new() -> Self {
+        Self {
+            effects: heapless::Vec::new(),
+        }
+    }
+
+    pub fn effects(&self) -> &[PostEffect] {
+        &self.effects
+    }
+
+    pub fn push(&mut self, effect: PostEffect) -> Result<(), PostEffect> {
+        self.effects.push(effect)
+    }
+
+    pub fn clear(&mut self) {
+        self.effects.clear();
+    }
+
+    // Decay replaces the clear at the start of the frame, so it is looked up
+    // separately from the rest of the chain
+    pub fn decay(&self) -> Option<f32> {
+        self.effects.iter().find_map(|e| match e {
+            PostEffect::Decay(keep) => Some(keep.clamp(0.0, 1.0)),
+            _ => None,
+        })
+    }
+
+    pub fn apply<const S: usize, const X: usize, const Y: usize>(&self, b: &mut RenderBuffer<S, X, Y>) {
+        for effect in self.effects.iter() {
+            match *effect {
+                PostEffect::Decay(_) => {}
+                PostEffect::BoxBlur => blur(b, [1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0]),
+                PostEffect::GaussianBlur => blur(b, [0.25, 0.5, 0.25]),
+                PostEffect::Mirror(axis) => mirror(b, axis),
+                PostEffect::Kaleidoscope => kaleidoscope(b),
+                PostEffect::Invert => map(b, |c| FixedColor { r: 1.0 - c.r, g: 1.0 - c.g, b: 1.0 - c.b, a: c.a }),
+                PostEffect::HueRotate(turns) => {
+                    let m = hue_matrix(turns);
+                    map(b, |c| rotate_hue(&m, c));
+                }
+            }
+        }
+    }
+}
+
+fn map<const S: usize, const X: usize, const Y: usize>(b: &mut RenderBuffer<S, X, Y>, f: impl Fn(FixedColor) -> FixedColor) {
+    for p in b.buffer_mut().iter_mut() {
+        *p = f(*p);
+    }
+}
+
+fn weighted(colors: [FixedColor; 3], kernel: [f32; 3]) -> FixedColor {
+    colors
+        .iter()
+        .zip(kernel.iter())
+        .fold(FixedColor::BLACK, |acc, (c, k)| acc.saturating_add(c.scale(*k)))
+}
+
+// Separable 3-tap blur, clamping at the edges. Only a row or column is copied
+// at a time, so this stays cheap on the device.
+fn blur<const S: usize, const X: usize, const Y: usize>(b: &mut RenderBuffer<S, X, Y>, kernel: [f32; 3]) {
+    for y in 0..Y as u32 {
+        let row: [FixedColor; X] = core::array::from_fn(|x| b.get_pixel(x as u32, y));
+        for x in 0..X {
+            let colors = [row[x.saturating_sub(1)], row[x], row[(x + 1).min(X - 1)]];
+            b.safe_set_pixel(x as u32, y, weighted(colors, kernel));
+        }
+    }
+    for x in 0..X as u32 {
+        let column: [FixedColor; Y] = core::array::from_fn(|y| b.get_pixel(x, y as u32));
+        for y in 0..Y {
+            let colors = [column[y.saturating_sub(1)], column[y], column[(y + 1).min(Y - 1)]];
+            b.safe_set_pixel(x, y as u32, weighted(colors, kernel));
+        }
+    }
+}
+
+fn mirror<const S: usize, const X: usize, const Y: usize>(b: &mut RenderBuffer<S, X, Y>, axis: Axis) {
+    let (w, h) = (X as u32, Y as u32);
+    match axis {
+        Axis::Horizontal => {
+            for y in 0..h {
+                for x in 0..w / 2 {
+                    b.safe_set_pixel(w - 1 - x, y, b.get_pixel(x, y));
+                }
+            }
+        }
+        Axis::Vertical => {
+            for y in 0..h / 2 {
+                for x in 0..w {
+                    b.safe_set_pixel(x, h - 1 - y, b.get_pixel(x, y));
+                }
+            }
+        }
+    }
+}
+
+// Eight-fold symmetry: the top left quadrant is folded along its diagonal,
+// then reflected into the other three quadrants.
+fn kaleidoscope<const S: usize, const X: usize, const Y: usize>(b: &mut RenderBuffer<S, X, Y>) {
+    let qw = (X as u32).div_ceil(2);
+    let qh = (Y as u32).div_ceil(2);
+
+    for y in 0..qh {
+        for x in 0..qw {
+            // Compare in normalised quadrant coordinates so non-square buffers still fold
+            let u = x as f32 / qw as f32;
+            let v = y as f32 / qh as f32;
+            if v > u {
+                let sx = ((v * qw as f32) as u32).min(qw - 1);
+                let sy = ((u * qh as f32) as u32).min(qh - 1);
+                b.safe_set_pixel(x, y, b.get_pixel(sx, sy));
+            }
+        }
+    }
+    mirror(b, Axis::Horizontal);
+    mirror(b, Axis::Vertical);
+}
+
+fn hue_matrix(turns: f32) -> [[f32; 3]; 3] {
+    let angle = turns * 2.0 * core::f32::consts::PI;
+    let (s, c) = (libm::sinf(angle), libm::cosf(angle));
+    let k = (1.0 - c) / 3.0;
+    let r = libm::sqrtf(1.0 / 3.0) * s;
+
+    [
+        [c + k, k - r, k + r],
+        [k + r, c + k, k - r],
+        [k - r, k + r, c + k],
+    ]
+}
+
+fn rotate_hue(m: &[[f32; 3]; 3], c: FixedColor) -> FixedColor {
+    let channel = |row: &[f32; 3]| (row[0] * c.r + row[1] * c.g + row[2] * c.b).clamp(0.0, 1.0);
+    FixedColor {
+        r: channel(&m[0]),
+        g: channel(&m[1]),
+        b: channel(&m[2]),
+        a: c.a,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Buffer = RenderBuffer<{ 4 * 4 }, 4, 4>;
+
+    fn chain(effects: &[PostEffect]) -> PostChain {
+        let mut chain = PostChain::new();
+        for e in effects {
+            chain.push(*e).unwrap();
+        }
+        chain
+    }
+
+    #[test]
+    fn test_decay_lookup() {
+        assert_eq!(chain(&[PostEffect::Invert]).decay(), None);
+        assert_eq!(chain(&[PostEffect::Invert, PostEffect::Decay(1.5)]).decay(), Some(1.0));
+    }
+
+    #[test]
+    fn test_mirror_and_invert() {
+        let mut b = Buffer::new();
+        b.safe_set_pixel(0, 1, FixedColor::RED);
+        chain(&[PostEffect::Mirror(Axis::Horizontal), PostEffect::Invert]).apply(&mut b);
+
+        assert_eq!(b.get_pixel(3, 1).as_rgb8(), (0, 255, 255));
+        assert_eq!(b.get_pixel(2, 2).as_rgb8(), (255, 255, 255));
+    }
+
+    #[test]
+    fn test_blur_spreads_light() {
+        let mut b = Buffer::new();
+        b.safe_set_pixel(1, 1, FixedColor::WHITE);
+        chain(&[PostEffect::GaussianBlur]).apply(&mut b);
+
+        let centre = b.get_pixel(1, 1).r;
+        assert!((centre - 0.25).abs() < 0.001);
+        assert!((b.get_pixel(2, 2).r - 0.0625).abs() < 0.001);
+        assert_eq!(b.get_pixel(3, 3).as_rgb8(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_kaleidoscope_symmetry() {
+        let mut b = Buffer::new();
+        b.safe_set_pixel(1, 0, FixedColor::GREEN);
+        chain(&[PostEffect::Kaleidoscope]).apply(&mut b);
+
+        for (x, y) in [(1, 0), (0, 1), (2, 0), (3, 1), (1, 3), (2, 3)] {
+            assert_eq!(b.get_pixel(x, y).as_rgb8(), (0, 255, 0));
+        }
+    }
+
+    #[test]
+    fn test_hue_rotate_third_turn() {
+        let mut b = Buffer::new();
+        b.clear_to_color(FixedColor::RED);
+        chain(&[PostEffect::HueRotate(1.0 / 3.0)]).apply(&mut b);
+
+        assert_eq!(b.get_pixel(0, 0).as_rgb8(), (0, 255, 0));
+    }
+}
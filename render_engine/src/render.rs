@@ -1,10 +1,20 @@
-use crate::renderbuffer::Blend;
+use crate::renderbuffer::{blend_merge, Blend};
 use crate::{UVec2, Vec2};
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 use az::Cast;
 
 use crate::fixedcolor::FixedColor;
+use crate::audio::{AudioFeatures, BeatFlash, VuMeter, Waterfall};
+use crate::automata::{Elementary, Life};
+use crate::beat::{Beat, Pulse};
+use crate::clock::Date;
+use crate::countdown::Countdown;
+use crate::games::{Input, Pong, Snake};
+use crate::font::{self, Orientation, TextColor, TextStyle};
+use crate::icicle::{Icicle, Matrix};
+use crate::noise::{Fractal, Noise, NoiseKind};
+use crate::particles::{ColorCurve, Emitter, Particle, ParticlePool};
 use crate::RenderBuffer;
 
 #[derive(Clone, Copy, PartialEq)]
@@ -12,12 +22,45 @@ pub enum RenderType {
     Sparkle,
     Snow,
     Rainbow,
+    Marquee,
+    Fire,
+    Fireworks,
+    Life,
+    // A 1D elementary automaton, by Wolfram rule number
+    Elementary(u8),
+    Plasma,
+    Aurora,
+    Icicle,
+    Matrix,
+    Countdown,
+    Snake,
+    Pong,
+    VuMeter,
+    BeatFlash,
+    Waterfall,
+    Pulse,
 }
 
 pub struct Renderers<const S: usize, const X: usize, const Y: usize> {
     sparkle: Sparkle<X, Y>,
-    snow: Snow<X, Y>,
+    snow: Snow<S, X, Y>,
     rainbow: Rainbow<X, Y>,
+    marquee: Marquee<X, Y>,
+    fire: Fire<X, Y>,
+    fireworks: Fireworks<X, Y>,
+    life: Life<X, Y>,
+    elementary: Elementary<X, Y>,
+    plasma: Plasma,
+    aurora: Aurora,
+    icicle: Icicle<X, Y>,
+    matrix: Matrix<X, Y>,
+    countdown: Countdown<X, Y>,
+    snake: Snake<X, Y>,
+    pong: Pong<X, Y>,
+    vu_meter: VuMeter<X, Y>,
+    beat_flash: BeatFlash,
+    waterfall: Waterfall<X, Y>,
+    pulse: Pulse,
 }
 
 impl<const S: usize, const X: usize, const Y: usize> Renderers<S, X, Y> {
@@ -26,14 +69,94 @@ impl<const S: usize, const X: usize, const Y: usize> Renderers<S, X, Y> {
             sparkle: Sparkle::new(),
             snow: Snow::new(),
             rainbow: Rainbow::new(),
+            marquee: Marquee::new(),
+            fire: Fire::new(),
+            fireworks: Fireworks::new(),
+            life: Life::new(),
+            elementary: Elementary::new(30),
+            plasma: Plasma::new(),
+            aurora: Aurora::new(),
+            icicle: Icicle::new(),
+            matrix: Matrix::new(),
+            countdown: Countdown::new(),
+            snake: Snake::new(),
+            pong: Pong::new(),
+            vu_meter: VuMeter::new(),
+            beat_flash: BeatFlash::new(),
+            waterfall: Waterfall::new(),
+            pulse: Pulse::new(),
+        }
+    }
+
+    pub fn set_marquee_text(&mut self, text: &str) {
+        self.marquee.set_text(text);
+    }
+
+    pub fn set_fire_settings(&mut self, settings: FireSettings) {
+        self.fire.settings = settings;
+    }
+
+    // Speed is the engine's, every other parameter belongs to an effect
+    pub fn set_param(&mut self, param: Param) {
+        match param {
+            Param::Speed(_) => {}
+            Param::FireCooling(cooling) => self.fire.settings.cooling = cooling,
+            Param::FireSparking(sparking) => self.fire.settings.sparking = sparking,
+        }
+    }
+
+    pub fn set_wall_clock(&mut self, now: Option<u64>) {
+        self.countdown.set_now(now);
+    }
+
+    pub fn set_countdown_target(&mut self, target: Option<Date>) {
+        self.countdown.set_target(target);
+    }
+
+    pub fn set_audio(&mut self, features: &AudioFeatures) {
+        self.vu_meter.set_audio(features);
+        self.beat_flash.set_audio(features);
+        self.waterfall.set_audio(features);
+    }
+
+    pub fn set_beat(&mut self, beat: Option<Beat>) {
+        self.sparkle.beat = beat;
+        self.pulse.set_beat(beat);
+    }
+
+    // Only the game being rendered hears the input
+    pub fn input(&mut self, renderer: RenderType, player: u8, input: Input) {
+        match renderer {
+            RenderType::Snake => self.snake.input(input),
+            RenderType::Pong => self.pong.input(player, input),
+            _ => {}
         }
     }
 
     pub fn step(&mut self, renderer: RenderType) {
         match renderer {
             RenderType::Sparkle => <Sparkle<X, Y> as Render<S, X, Y>>::step(&mut self.sparkle),
-            RenderType::Snow => <Snow<X, Y> as Render<S, X, Y>>::step(&mut self.snow),
+            RenderType::Snow => <Snow<S, X, Y> as Render<S, X, Y>>::step(&mut self.snow),
             RenderType::Rainbow => <Rainbow<X, Y> as Render<S, X, Y>>::step(&mut self.rainbow),
+            RenderType::Marquee => <Marquee<X, Y> as Render<S, X, Y>>::step(&mut self.marquee),
+            RenderType::Fire => <Fire<X, Y> as Render<S, X, Y>>::step(&mut self.fire),
+            RenderType::Fireworks => <Fireworks<X, Y> as Render<S, X, Y>>::step(&mut self.fireworks),
+            RenderType::Life => <Life<X, Y> as Render<S, X, Y>>::step(&mut self.life),
+            RenderType::Plasma => <Plasma as Render<S, X, Y>>::step(&mut self.plasma),
+            RenderType::Aurora => <Aurora as Render<S, X, Y>>::step(&mut self.aurora),
+            RenderType::Icicle => <Icicle<X, Y> as Render<S, X, Y>>::step(&mut self.icicle),
+            RenderType::Matrix => <Matrix<X, Y> as Render<S, X, Y>>::step(&mut self.matrix),
+            RenderType::Countdown => <Countdown<X, Y> as Render<S, X, Y>>::step(&mut self.countdown),
+            RenderType::Snake => <Snake<X, Y> as Render<S, X, Y>>::step(&mut self.snake),
+            RenderType::Pong => <Pong<X, Y> as Render<S, X, Y>>::step(&mut self.pong),
+            RenderType::VuMeter => <VuMeter<X, Y> as Render<S, X, Y>>::step(&mut self.vu_meter),
+            RenderType::BeatFlash => <BeatFlash as Render<S, X, Y>>::step(&mut self.beat_flash),
+            RenderType::Waterfall => <Waterfall<X, Y> as Render<S, X, Y>>::step(&mut self.waterfall),
+            RenderType::Pulse => <Pulse as Render<S, X, Y>>::step(&mut self.pulse),
+            RenderType::Elementary(rule) => {
+                self.elementary.set_rule(rule);
+                <Elementary<X, Y> as Render<S, X, Y>>::step(&mut self.elementary)
+            }
         }
     }
 
@@ -42,6 +165,22 @@ impl<const S: usize, const X: usize, const Y: usize> Renderers<S, X, Y> {
             RenderType::Sparkle => self.sparkle.render(t, dt, buffer, blend),
             RenderType::Snow => self.snow.render(t, dt, buffer, blend),
             RenderType::Rainbow => self.rainbow.render(t, dt, buffer, blend),
+            RenderType::Marquee => self.marquee.render(t, dt, buffer, blend),
+            RenderType::Fire => self.fire.render(t, dt, buffer, blend),
+            RenderType::Fireworks => self.fireworks.render(t, dt, buffer, blend),
+            RenderType::Life => self.life.render(t, dt, buffer, blend),
+            RenderType::Plasma => self.plasma.render(t, dt, buffer, blend),
+            RenderType::Aurora => self.aurora.render(t, dt, buffer, blend),
+            RenderType::Icicle => self.icicle.render(t, dt, buffer, blend),
+            RenderType::Matrix => self.matrix.render(t, dt, buffer, blend),
+            RenderType::Countdown => self.countdown.render(t, dt, buffer, blend),
+            RenderType::Snake => self.snake.render(t, dt, buffer, blend),
+            RenderType::Pong => self.pong.render(t, dt, buffer, blend),
+            RenderType::VuMeter => self.vu_meter.render(t, dt, buffer, blend),
+            RenderType::BeatFlash => self.beat_flash.render(t, dt, buffer, blend),
+            RenderType::Waterfall => self.waterfall.render(t, dt, buffer, blend),
+            RenderType::Pulse => self.pulse.render(t, dt, buffer, blend),
+            RenderType::Elementary(_) => self.elementary.render(t, dt, buffer, blend),
         }
     }
 }
@@ -79,8 +218,14 @@ impl SparklePoint {
 
 const NUM_SPARKLE_POINTS: usize = 20;
 
+// Extra points lit on every beat while the beat clock is running
+const NUM_BURST_POINTS: usize = 12;
+const BURST_DECAY: f32 = 0.85;
+
 struct Sparkle<const X: usize, const Y: usize> {
     points: [SparklePoint; NUM_SPARKLE_POINTS],
+    burst: [SparklePoint; NUM_BURST_POINTS],
+    beat: Option<Beat>,
     rng: SmallRng,
 }
 
@@ -90,6 +235,13 @@ impl <const X: usize, const Y: usize> Sparkle<X, Y> {
 
         Self {
             points: core::array::from_fn(|_| SparklePoint::random_pos(&mut rng, X as u32, Y as u32)),
+            burst: core::array::from_fn(|_| SparklePoint {
+                pos: UVec2::new(0, 0),
+                color: FixedColor::BLACK,
+                phase: 0.0,
+                speed: 0.0,
+            }),
+            beat: None,
             rng,
         }
     }
@@ -106,6 +258,18 @@ impl<const S:usize, const X: usize, const Y: usize> Render<S, X, Y> for Sparkle<
                 point.phase = 0.0;
             }
         }
+
+        if self.beat.is_some_and(|beat| beat.on_beat) {
+            for point in self.burst.iter_mut() {
+                *point = SparklePoint::random_pos(&mut self.rng, X as u32, Y as u32);
+                point.color = FixedColor::from_hsv(self.rng.gen(), 0.5, 1.0);
+                point.phase = 1.0;
+            }
+        } else {
+            for point in self.burst.iter_mut() {
+                point.phase *= BURST_DECAY;
+            }
+        }
     }
 
     fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
@@ -113,17 +277,36 @@ impl<const S:usize, const X: usize, const Y: usize> Render<S, X, Y> for Sparkle<
             let colour = point.color.scale(point.phase.cast());
             buffer.safe_set_pixel(point.pos.x, point.pos.y, colour);
         }
+
+        for point in self.burst.iter().filter(|point| point.phase > 0.01) {
+            let colour = point.color.scale(point.phase.cast());
+            buffer.safe_set_pixel(point.pos.x, point.pos.y, colour);
+        }
     }
 }
 // -----
 
-const NUM_SNOWFLAKES: usize = 30;
+// One snowflake for every SNOWFLAKE_DENSITY LEDs. S / SNOWFLAKE_DENSITY can't
+// be an array length while S is generic, so each flake is packed into
+// SNOWFLAKE_DENSITY bytes instead and the flakes fill a byte per LED.
+const SNOWFLAKE_DENSITY: usize = 6;
 const MAX_SNOWFLAKE_SPEED: f32 = 0.5;
 const MIN_SNOWFLAKE_SPEED: f32 = 0.1;
+// Strongest wind in pixels per step, and the chance each step of a new gust
+const MAX_WIND: f32 = 0.3;
+const GUST_CHANCE: f32 = 0.01;
+// How quickly the wind eases towards the strength of the latest gust
+const WIND_EASE: f32 = 0.02;
+// Height added to the pile by every landing flake, in pixels
+const PILE_PER_FLAKE: f32 = 0.1;
+// Fraction of the display the pile can fill before it melts away
+const MAX_PILE: f32 = 0.25;
+const MELT_RATE: f32 = 0.01;
+const PILE_COLOR: FixedColor = FixedColor::rgb(0.8, 0.85, 1.0);
+
 struct SnowFlake {
     pos: Vec2,
     speed: f32,
-    color: FixedColor,
 }
 
 impl SnowFlake {
@@ -131,58 +314,142 @@ impl SnowFlake {
         let min = MIN_SNOWFLAKE_SPEED;
         let max = MAX_SNOWFLAKE_SPEED;
 
-        let speed = rng.gen_range(min..max);
-        let scale = (speed - min) / (max - min);
-        let color = FixedColor::WHITE.scale(scale);
-
-
         Self { 
             pos:  Vec2 {
                 x: rng.gen_range(0..x_max) as f32,
                 y: rng.gen_range(0..y_max) as f32,
             },
-            speed,
-            color,
+            speed: rng.gen_range(min..max),
         }
     }
 
-    fn new_randon_top(&mut self, rng: &mut SmallRng, x: usize) {
+    // Faster flakes are brighter
+    fn color(&self) -> FixedColor {
+        let scale = (self.speed - MIN_SNOWFLAKE_SPEED) / (MAX_SNOWFLAKE_SPEED - MIN_SNOWFLAKE_SPEED);
+        FixedColor::WHITE.scale(scale)
+    }
+
+    fn new_random_top(&mut self, rng: &mut SmallRng, x: usize) {
         self.pos = Vec2 {
             x: rng.gen_range(0..x) as f32,
             y: 0.0,
         };
     }
+
+    // The position and speed as 16 bit fractions of their ranges, which is
+    // finer than a flake moves in a step
+    fn pack(&self, bytes: &mut [u8], x_max: usize, y_max: usize) {
+        let fractions = [
+            self.pos.x / x_max as f32,
+            self.pos.y / y_max as f32,
+            (self.speed - MIN_SNOWFLAKE_SPEED) / (MAX_SNOWFLAKE_SPEED - MIN_SNOWFLAKE_SPEED),
+        ];
+        for (chunk, fraction) in bytes.chunks_exact_mut(2).zip(fractions) {
+            let packed = libm::roundf(fraction * 65536.0).clamp(0.0, u16::MAX as f32) as u16;
+            chunk.copy_from_slice(&packed.to_le_bytes());
+        }
+    }
+
+    fn unpack(bytes: &[u8], x_max: usize, y_max: usize) -> Self {
+        let fraction = |i: usize| u16::from_le_bytes([bytes[2 * i], bytes[2 * i + 1]]) as f32 / 65536.0;
+        Self {
+            pos: Vec2 {
+                x: fraction(0) * x_max as f32,
+                y: fraction(1) * y_max as f32,
+            },
+            speed: MIN_SNOWFLAKE_SPEED + fraction(2) * (MAX_SNOWFLAKE_SPEED - MIN_SNOWFLAKE_SPEED),
+        }
+    }
 }
 
-struct Snow<const X: usize, const Y: usize> {
-    // Would like to make NUM_SNOWFLAKES something like X * Y / 6
-    snowflakes: [SnowFlake; NUM_SNOWFLAKES],
+struct Snow<const S: usize, const X: usize, const Y: usize> {
+    // Packed flakes, SNOWFLAKE_DENSITY bytes each
+    snowflakes: [u8; S],
+    wind: f32,
+    // The strength the wind is easing towards
+    gust: f32,
+    // Height of the snow pile at the bottom of each drop, in pixels
+    pile: [f32; X],
+    melting: bool,
     rng: SmallRng,
 }
 
-impl<const X: usize, const Y: usize> Snow<X, Y> {
+impl<const S: usize, const X: usize, const Y: usize> Snow<S, X, Y> {
     fn new() -> Self {
         let mut rng = SmallRng::seed_from_u64(0);
+        let mut snowflakes = [0; S];
+        for bytes in snowflakes.chunks_exact_mut(SNOWFLAKE_DENSITY) {
+            SnowFlake::new_random(&mut rng, X, Y).pack(bytes, X, Y);
+        }
 
         Self {
-            snowflakes: core::array::from_fn(|_| SnowFlake::new_random(&mut rng, X, Y)),
+            snowflakes,
+            wind: 0.0,
+            gust: 0.0,
+            pile: [0.0; X],
+            melting: false,
             rng,
         }
     }
+
+    fn snowflakes(&self) -> impl Iterator<Item = SnowFlake> + '_ {
+        self.snowflakes.chunks_exact(SNOWFLAKE_DENSITY).map(|bytes| SnowFlake::unpack(bytes, X, Y))
+    }
+
+    // Add a flake to the pile, letting it slide off onto a lower neighbour
+    // so the pile doesn't grow into spikes
+    fn land(pile: &mut [f32; X], x: usize) {
+        pile[x] += PILE_PER_FLAKE;
+        for neighbour in [x.wrapping_sub(1), x + 1] {
+            if neighbour < X && pile[x] - pile[neighbour] > 1.0 {
+                let slide = (pile[x] - pile[neighbour]) / 2.0;
+                pile[x] -= slide;
+                pile[neighbour] += slide;
+            }
+        }
+    }
 }
 
-impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Snow<X, Y> {
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Snow<S, X, Y> {
     fn step(&mut self) {
-        for snowflake in self.snowflakes.iter_mut() {
+        if self.rng.gen::<f32>() < GUST_CHANCE {
+            self.gust = self.rng.gen_range(-MAX_WIND..MAX_WIND);
+        }
+        self.wind += (self.gust - self.wind) * WIND_EASE;
+
+        for bytes in self.snowflakes.chunks_exact_mut(SNOWFLAKE_DENSITY) {
+            let mut snowflake = SnowFlake::unpack(bytes, X, Y);
             snowflake.pos.y += snowflake.speed;
-            if snowflake.pos.y > Y as f32 {
-                snowflake.new_randon_top(&mut self.rng, X);
+            // Slower, lighter flakes are blown about more
+            let drift = 1.5 - snowflake.speed / MAX_SNOWFLAKE_SPEED;
+            let x = libm::fmodf(snowflake.pos.x + self.wind * drift, X as f32);
+            snowflake.pos.x = if x < 0.0 { x + X as f32 } else { x };
+
+            let column = (snowflake.pos.x as usize).min(X - 1);
+            if snowflake.pos.y > Y as f32 - self.pile[column] {
+                if !self.melting {
+                    Self::land(&mut self.pile, column);
+                }
+                snowflake.new_random_top(&mut self.rng, X);
+            }
+            snowflake.pack(bytes, X, Y);
+        }
+
+        // Once the pile is deep enough it melts all the way away
+        let limit = Y as f32 * MAX_PILE;
+        if self.pile.iter().any(|height| *height >= limit) {
+            self.melting = true;
+        }
+        if self.melting {
+            for height in self.pile.iter_mut() {
+                *height = (*height - MELT_RATE).max(0.0);
             }
+            self.melting = self.pile.iter().any(|height| *height > 0.0);
         }
     }
 
     fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, blend: Blend) {
-        for snowflake in self.snowflakes.iter() {
+        for snowflake in self.snowflakes() {
 
             let one = 1.0;
             let phase = snowflake.pos.y % 1.0;
@@ -190,9 +457,21 @@ impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Snow<X,
             let x: u32 = snowflake.pos.x.cast();
             let y: u32 = snowflake.pos.y.cast();
 
-            buffer.safe_set_max_rgb(x, y, snowflake.color.scale(one - phase), blend);
+            buffer.safe_set_max_rgb(x, y, snowflake.color().scale(one - phase), blend);
             if phase >0.0 {
-                buffer.safe_set_max_rgb(x, y+1, snowflake.color.scale(phase), blend);
+                buffer.safe_set_max_rgb(x, y+1, snowflake.color().scale(phase), blend);
+            }
+        }
+
+        for (x, height) in self.pile.iter().enumerate() {
+            let full = libm::floorf(*height);
+            for k in 0..full as u32 {
+                buffer.safe_set_max_rgb(x as u32, Y as u32 - 1 - k, PILE_COLOR, blend);
+            }
+            // The top of the pile is only partly lit
+            if full < Y as f32 {
+                let top = Y as u32 - 1 - full as u32;
+                buffer.safe_set_max_rgb(x as u32, top, PILE_COLOR.scale(*height - full), blend);
             }
         }
     }
@@ -231,3 +510,469 @@ impl<const S: usize, const X: usize, const Y: usize>  Render<S, X, Y> for Rainbo
         }
     }
 }
+
+// -----
+
+pub const MARQUEE_CAPACITY: usize = 64;
+pub type MarqueeText = heapless::String<MARQUEE_CAPACITY>;
+
+// As much of the text as fits in the marquee
+pub fn marquee_text(text: &str) -> MarqueeText {
+    let mut marquee_text = MarqueeText::new();
+    for c in text.chars() {
+        if marquee_text.push(c).is_err() {
+            break;
+        }
+    }
+    marquee_text
+}
+
+const MARQUEE_SPEED: f32 = 0.5;
+const MARQUEE_PALETTE: [FixedColor; 3] = [FixedColor::RED, FixedColor::GREEN, FixedColor::WHITE];
+
+struct Marquee<const X: usize, const Y: usize> {
+    text: MarqueeText,
+    offset: f32,
+}
+
+impl<const X: usize, const Y: usize> Marquee<X, Y> {
+    fn new() -> Self {
+        let mut marquee = Self {
+            text: MarqueeText::new(),
+            offset: 0.0,
+        };
+        marquee.set_text("MERRY CHRISTMAS");
+        marquee
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.text = marquee_text(text);
+        self.offset = 0.0;
+    }
+
+    // Wide buffers scroll right to left, tall ones (a handful of drops) scroll
+    // up with the text reading down the drops.
+    fn style() -> TextStyle<'static> {
+        let (orientation, height) = if X >= Y {
+            (Orientation::Horizontal, Y)
+        } else {
+            (Orientation::Vertical, X)
+        };
+        TextStyle::new(TextColor::Palette(&MARQUEE_PALETTE))
+            .with_orientation(orientation)
+            .with_scale(TextStyle::scale_for_height(height as u32))
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Marquee<X, Y> {
+    fn step(&mut self) {
+        let style = Self::style();
+        let length = match style.orientation {
+            Orientation::Horizontal => X,
+            Orientation::Vertical => Y,
+        };
+        // Scroll until the end of the text has left the display
+        let distance = (font::text_width(&self.text, style.scale) as usize + length) as f32;
+
+        self.offset += MARQUEE_SPEED;
+        if self.offset > distance {
+            self.offset = 0.0;
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        let style = Self::style();
+        let glyph_size = (font::GLYPH_HEIGHT * style.scale) as i32;
+        let offset: i32 = self.offset.cast();
+
+        let (x, y) = match style.orientation {
+            Orientation::Horizontal => (X as i32 - offset, (Y as i32 - glyph_size) / 2),
+            Orientation::Vertical => ((X as i32 - glyph_size) / 2, Y as i32 - offset),
+        };
+        font::draw_text(buffer, &self.text, x, y, &style);
+    }
+}
+
+// -----
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FireSettings {
+    // How quickly the flames cool as they rise, higher gives shorter flames
+    pub cooling: u8,
+    // Chance out of 255 that a new spark starts at the base of a drop each step
+    pub sparking: u8,
+}
+
+impl Default for FireSettings {
+    fn default() -> Self {
+        Self {
+            cooling: 55,
+            sparking: 120,
+        }
+    }
+}
+
+// Settings that can be changed while an effect runs
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Param {
+    // How fast every effect runs, 1.0 as designed and 0.0 frozen
+    Speed(f32),
+    FireCooling(u8),
+    FireSparking(u8),
+}
+
+// Sparks only start in the lowest few LEDs of a drop
+const FIRE_SPARK_ROWS: usize = 4;
+
+// Heat diffusion per drop, based on the classic Fire2012 simulation. The base
+// of the fire is the bottom of each drop, heat rises towards the top.
+struct Fire<const X: usize, const Y: usize> {
+    // heat[x][0] is the bottom of drop x
+    heat: [[u8; Y]; X],
+    settings: FireSettings,
+    rng: SmallRng,
+}
+
+impl<const X: usize, const Y: usize> Fire<X, Y> {
+    fn new() -> Self {
+        Self {
+            heat: [[0; Y]; X],
+            settings: FireSettings::default(),
+            rng: SmallRng::seed_from_u64(0),
+        }
+    }
+
+    // Black through red and yellow to white
+    fn heat_color(heat: u8) -> FixedColor {
+        let t192 = (heat as u32 * 191 / 255) as u8;
+        let ramp = (t192 & 0x3f) << 2;
+        if t192 & 0x80 != 0 {
+            FixedColor::from_rgb8(255, 255, ramp)
+        } else if t192 & 0x40 != 0 {
+            FixedColor::from_rgb8(255, ramp, 0)
+        } else {
+            FixedColor::from_rgb8(ramp, 0, 0)
+        }
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Fire<X, Y> {
+    fn step(&mut self) {
+        // Short drops would cool by more than a u8 holds
+        let max_cooling = (self.settings.cooling as usize * 10 / Y + 2).min(u8::MAX as usize) as u8;
+
+        for column in self.heat.iter_mut() {
+            // Every cell cools down a little
+            for cell in column.iter_mut() {
+                *cell = cell.saturating_sub(self.rng.gen_range(0..=max_cooling));
+            }
+
+            // Heat drifts up and diffuses
+            for k in (2..Y).rev() {
+                column[k] = ((column[k - 1] as u16 + 2 * column[k - 2] as u16) / 3) as u8;
+            }
+
+            // Randomly ignite new sparks near the bottom
+            if self.rng.gen::<u8>() < self.settings.sparking {
+                let k = self.rng.gen_range(0..FIRE_SPARK_ROWS.min(Y));
+                column[k] = column[k].saturating_add(self.rng.gen_range(160..=255));
+            }
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        for (x, column) in self.heat.iter().enumerate() {
+            for (k, heat) in column.iter().enumerate() {
+                let y = Y - 1 - k;
+                buffer.safe_set_pixel(x as u32, y as u32, Self::heat_color(*heat));
+            }
+        }
+    }
+}
+
+// -----
+
+const NUM_ROCKETS: usize = 4;
+const NUM_SPARKS: usize = 96;
+const SPARKS_PER_BURST: usize = 32;
+// Chance each step of launching a new rocket
+const ROCKET_LAUNCH_CHANCE: f32 = 0.04;
+const ROCKET_COLOR: FixedColor = FixedColor::rgb(1.0, 0.8, 0.5);
+const FIREWORK_COLORS: [FixedColor; 5] = [
+    FixedColor::RED,
+    FixedColor::GREEN,
+    FixedColor::BLUE,
+    FixedColor::rgb(1.0, 0.8, 0.0),
+    FixedColor::rgb(0.8, 0.2, 1.0),
+];
+// Sparks flash white, settle to their own colour, then fade
+const SPARK_CURVE: ColorCurve<'static> = ColorCurve::new(&[
+    (0.0, FixedColor::WHITE),
+    (0.2, FixedColor::WHITE),
+    (0.5, FixedColor::rgb(0.7, 0.7, 0.7)),
+    (1.0, FixedColor::BLACK),
+]);
+const ROCKET_CURVE: ColorCurve<'static> = ColorCurve::new(&[(0.0, FixedColor::WHITE)]);
+
+// Rockets rise from the bottom of the display and burst into fading sparks
+pub(crate) struct Fireworks<const X: usize, const Y: usize> {
+    rockets: ParticlePool<NUM_ROCKETS>,
+    sparks: ParticlePool<NUM_SPARKS>,
+    rng: SmallRng,
+}
+
+impl<const X: usize, const Y: usize> Fireworks<X, Y> {
+    pub(crate) fn new() -> Self {
+        Self {
+            rockets: ParticlePool::with_physics(0.02, 0.0),
+            sparks: ParticlePool::with_physics(0.01, 0.06),
+            rng: SmallRng::seed_from_u64(0),
+        }
+    }
+
+    fn launch(&mut self) {
+        // Enough speed to reach somewhere in the top half before gravity wins
+        let height = Y as f32;
+        let apex = self.rng.gen_range(0.5..0.9) * height;
+        let speed = libm::sqrtf(2.0 * self.rockets.gravity * apex);
+        let x = self.rng.gen_range(0..X) as f32 + 0.5;
+        let rocket = Particle::new(x, height - 0.5, 0.0, -speed, 2.0 * height / speed.max(0.01), ROCKET_COLOR);
+        self.rockets.spawn(rocket);
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Fireworks<X, Y> {
+    fn step(&mut self) {
+        if self.rng.gen::<f32>() < ROCKET_LAUNCH_CHANCE {
+            self.launch();
+        }
+
+        self.rockets.step();
+        self.sparks.step();
+
+        // Burst any rocket that has stopped climbing
+        for rocket in self.rockets.iter_mut() {
+            if rocket.vy >= 0.0 {
+                rocket.kill();
+                let emitter = Emitter {
+                    x: rocket.x,
+                    y: rocket.y,
+                    angle: 0.0..1.0,
+                    speed: 0.1..0.5,
+                    lifetime: 15.0..35.0,
+                    color: FIREWORK_COLORS[self.rng.gen_range(0..FIREWORK_COLORS.len())],
+                };
+                emitter.burst(&mut self.sparks, &mut self.rng, SPARKS_PER_BURST);
+            }
+        }
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        self.sparks.render(buffer, &SPARK_CURVE);
+        self.rockets.render(buffer, &ROCKET_CURVE);
+    }
+}
+
+// -----
+
+// Pixels per noise cell, so features are a few LEDs across
+const PLASMA_SCALE: f32 = 0.12;
+const PLASMA_FRACTAL: Fractal = Fractal::new(NoiseKind::Simplex, 3);
+const PLASMA_SPEED: f32 = 0.01;
+
+// Slowly morphing bands of colour, hue taken from fractal noise
+struct Plasma {
+    noise: Noise,
+    time: f32,
+}
+
+impl Plasma {
+    fn new() -> Self {
+        Self {
+            noise: Noise::new(0),
+            time: 0.0,
+        }
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Plasma {
+    fn step(&mut self) {
+        self.time += PLASMA_SPEED;
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        for y in 0..Y {
+            for x in 0..X {
+                let (nx, ny) = (x as f32 * PLASMA_SCALE, y as f32 * PLASMA_SCALE);
+                let n = self.noise.fractal3(&PLASMA_FRACTAL, nx, ny, self.time);
+                // Let the whole palette drift as well as the shapes
+                let hue = n * 0.75 + self.time * 0.5;
+                buffer.safe_set_pixel(x as u32, y as u32, FixedColor::from_hsv(hue, 1.0, 1.0));
+            }
+        }
+    }
+}
+
+const AURORA_SPEED: f32 = 0.004;
+const AURORA_FRACTAL: Fractal = Fractal::new(NoiseKind::Perlin, 3);
+const AURORA_GREEN: FixedColor = FixedColor::rgb(0.1, 1.0, 0.4);
+const AURORA_PURPLE: FixedColor = FixedColor::rgb(0.6, 0.1, 0.9);
+
+// Curtains of green and purple hanging from the top of the display
+struct Aurora {
+    noise: Noise,
+    time: f32,
+}
+
+impl Aurora {
+    fn new() -> Self {
+        Self {
+            noise: Noise::new(1),
+            time: 0.0,
+        }
+    }
+}
+
+impl<const S: usize, const X: usize, const Y: usize> Render<S, X, Y> for Aurora {
+    fn step(&mut self) {
+        self.time += AURORA_SPEED;
+    }
+
+    fn render(&self, _t: f32, _dt: f32, buffer: &mut RenderBuffer<S, X, Y>, _blend: Blend) {
+        for x in 0..X {
+            let nx = x as f32 * 0.08;
+            // How bright, how far down, and which colour each curtain is
+            let intensity = self.noise.fractal2(&AURORA_FRACTAL, nx, self.time) * 0.5 + 0.5;
+            let length = (self.noise.perlin2(nx * 0.5 + 10.0, self.time * 0.7) * 0.5 + 0.5) * 0.7 + 0.2;
+            let tint = (self.noise.perlin2(nx * 0.3 + 20.0, self.time * 0.5) * 0.5 + 0.5).clamp(0.0, 1.0);
+            let color = blend_merge(AURORA_GREEN, AURORA_PURPLE, tint);
+
+            for y in 0..Y {
+                let depth = y as f32 / (Y as f32 * length);
+                if depth >= 1.0 {
+                    break;
+                }
+                // Shimmer running down each curtain
+                let shimmer = self.noise.value3(nx * 4.0, y as f32 * 0.3, self.time * 8.0) * 0.2 + 0.8;
+                let brightness = intensity * intensity * (1.0 - depth) * shimmer;
+                buffer.safe_set_pixel(x as u32, y as u32, color.scale(brightness));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fire_rises_from_the_bottom() {
+        type Buffer = RenderBuffer<{ 5 * 24 }, 5, 24>;
+        let mut fire = Fire::<5, 24>::new();
+        fire.settings.sparking = 255;
+        for _ in 0..20 {
+            <Fire<5, 24> as Render<{ 5 * 24 }, 5, 24>>::step(&mut fire);
+        }
+
+        let mut buffer = Buffer::new();
+        fire.render(0.0, 0.0, &mut buffer, Blend::Dest);
+
+        let brightness = |y: u32| -> f32 { (0..5).map(|x| buffer.get_pixel(x, y).r).sum() };
+        assert!(brightness(23) > brightness(0));
+        assert!(brightness(23) > 0.0);
+    }
+
+    #[test]
+    fn test_short_fire_still_cools() {
+        let mut fire = Fire::<4, 5>::new();
+        fire.settings = FireSettings { cooling: 255, sparking: 0 };
+        fire.heat = [[200; 5]; 4];
+        <Fire<4, 5> as Render<{ 4 * 5 }, 4, 5>>::step(&mut fire);
+        let total: u32 = fire.heat.iter().flatten().map(|heat| *heat as u32).sum();
+        assert!(total < 200 * 4 * 5);
+    }
+
+    #[test]
+    fn test_fireworks_burst() {
+        let mut fireworks = Fireworks::<10, 24>::new();
+        let mut burst = false;
+        for _ in 0..500 {
+            <Fireworks<10, 24> as Render<{ 10 * 24 }, 10, 24>>::step(&mut fireworks);
+            burst |= fireworks.sparks.alive() > 0;
+        }
+        assert!(burst);
+    }
+
+    #[test]
+    fn test_aurora_hangs_from_the_top() {
+        type Buffer = RenderBuffer<{ 50 * 24 }, 50, 24>;
+        let aurora = Aurora::new();
+        let mut buffer = Buffer::new();
+        aurora.render(0.0, 0.0, &mut buffer, Blend::Dest);
+
+        let row = |y: u32| -> f32 { (0..50).map(|x| buffer.get_pixel(x, y).g).sum() };
+        assert!(row(0) > 0.0);
+        assert!(row(0) > row(12));
+        assert_eq!(row(23), 0.0);
+    }
+
+    #[test]
+    fn test_snowflake_count_follows_buffer_size() {
+        assert_eq!(Snow::<{ 50 * 24 }, 50, 24>::new().snowflakes().count(), 200);
+        assert_eq!(Snow::<{ 5 * 24 }, 5, 24>::new().snowflakes().count(), 20);
+        assert_eq!(Snow::<{ 7 * 7 }, 7, 7>::new().snowflakes().count(), 8);
+    }
+
+    #[test]
+    fn test_snowflake_packing() {
+        let flake = SnowFlake { pos: Vec2 { x: 49.3, y: 12.75 }, speed: 0.25 };
+        let mut bytes = [0; SNOWFLAKE_DENSITY];
+        flake.pack(&mut bytes, 50, 24);
+        let unpacked = SnowFlake::unpack(&bytes, 50, 24);
+        assert!((unpacked.pos.x - 49.3).abs() < 0.001);
+        assert!((unpacked.pos.y - 12.75).abs() < 0.001);
+        assert!((unpacked.speed - 0.25).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_snow_piles_up_then_melts() {
+        let mut snow = Snow::<{ 5 * 24 }, 5, 24>::new();
+        let mut peak = 0.0f32;
+        let mut melted = false;
+        for _ in 0..20000 {
+            <Snow<{ 5 * 24 }, 5, 24> as Render<{ 5 * 24 }, 5, 24>>::step(&mut snow);
+            let height = snow.pile.iter().cloned().fold(0.0, f32::max);
+            peak = peak.max(height);
+            melted |= snow.melting;
+            assert!(height <= 24.0 * MAX_PILE + 1.0);
+            assert!(snow.snowflakes().all(|f| f.pos.x >= 0.0 && f.pos.x < 5.0));
+        }
+        assert!(peak >= 24.0 * MAX_PILE);
+        assert!(melted);
+    }
+
+    #[test]
+    fn test_pile_slides() {
+        let mut pile = [0.0, 1.0, 0.0];
+        Snow::<{ 3 * 24 }, 3, 24>::land(&mut pile, 1);
+        assert!(pile[1] <= 1.0 && pile[0] > 0.0);
+    }
+
+    #[test]
+    fn test_marquee_text_keeps_what_fits() {
+        assert_eq!(marquee_text("HO HO HO").as_str(), "HO HO HO");
+        // Cut at a whole character, never part way through one
+        let long: heapless::String<128> = core::iter::repeat_n('é', MARQUEE_CAPACITY).collect();
+        let text = marquee_text(&long);
+        assert_eq!(text.chars().count(), MARQUEE_CAPACITY / 2);
+        assert!(text.chars().all(|c| c == 'é'));
+    }
+
+    #[test]
+    fn test_heat_palette() {
+        assert_eq!(Fire::<1, 1>::heat_color(0).as_rgb8(), (0, 0, 0));
+        assert_eq!(Fire::<1, 1>::heat_color(255).as_rgb8(), (255, 255, 252));
+        let (r, g, b) = Fire::<1, 1>::heat_color(100).as_rgb8();
+        assert!(r > g && b == 0);
+    }
+}
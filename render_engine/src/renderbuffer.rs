@@ -1,12 +1,14 @@
 
 use crate::{fixedcolor, UVec2};
 use crate::fixedcolor::FixedColor;
+use crate::sprite::{BlitOptions, Sprite};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "serde")]
 use serde_with::serde_as;
 
 // Use of cfg_eval explained [here](https://docs.rs/serde_with/latest/serde_with/guide/serde_as/index.html#gating-serde_as-on-features)
+#[derive(Clone)]
 #[cfg_attr(feature = "serde", cfg_eval::cfg_eval, serde_as, derive(Serialize, Deserialize))]
 pub struct RenderBuffer<const S: usize, const X: usize, const Y: usize> {
     size: UVec2,
@@ -47,6 +49,13 @@ impl<const S: usize, const X:usize, const Y:usize> RenderBuffer<S, X, Y> {
         self.clear_to_color(FixedColor::default());
     }
 
+    // Scale every pixel towards black, keeping `keep` of its brightness
+    pub fn fade(&mut self, keep: f32) {
+        for p in self.buffer_mut().iter_mut() {
+            *p = p.scale(keep);
+        }
+    }
+
     pub fn clear_to_color(&mut self, color: FixedColor) {
         for p in self.buffer_mut().iter_mut() {
             *p = color;
@@ -69,6 +78,12 @@ impl<const S: usize, const X:usize, const Y:usize> RenderBuffer<S, X, Y> {
         }
     }
 
+    pub fn safe_set_pixel_signed(&mut self, x: i32, y: i32, color: FixedColor) {
+        if x >= 0 && y >= 0 {
+            self.safe_set_pixel(x as u32, y as u32, color);
+        }
+    }
+
     pub fn safe_set_max_rgb(&mut self, x: u32, y: u32, color: FixedColor, _blend: Blend) {
         if x < X as u32 && y < Y as u32 {
             let current = self.get_pixel(x, y);
@@ -82,6 +97,65 @@ impl<const S: usize, const X:usize, const Y:usize> RenderBuffer<S, X, Y> {
         }
     }
 
+    // Mix `color` over the current pixel using `weight` as its opacity
+    pub fn blend_over(&mut self, x: i32, y: i32, color: FixedColor, weight: f32) {
+        if x >= 0 && y >= 0 && x < X as i32 && y < Y as i32 && weight > 0.0 {
+            let current = self.get_pixel(x as u32, y as u32);
+            let new_color = blend_merge(current, color, weight.min(1.0));
+            self.safe_set_pixel(x as u32, y as u32, new_color);
+        }
+    }
+
+    pub fn blit(&mut self, sprite: &Sprite, frame: u32, x: i32, y: i32, options: &BlitOptions) {
+        for sy in 0..sprite.height() {
+            for sx in 0..sprite.width() {
+                let color = options.source(sprite, frame, sx, sy);
+                self.blend_over(x + sx as i32, y + sy as i32, color, color.a);
+            }
+        }
+    }
+
+    // Each sprite pixel is spread over the four buffer pixels it overlaps. The
+    // coverage of every buffer pixel is summed from the sprite pixels over it
+    // first, then blended once, so an opaque sprite stays opaque.
+    pub fn blit_subpixel(&mut self, sprite: &Sprite, frame: u32, x: f32, y: f32, options: &BlitOptions) {
+        let x0 = libm::floorf(x);
+        let y0 = libm::floorf(y);
+        let fx = x - x0;
+        let fy = y - y0;
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        // Offset of the sprite pixel from the buffer pixel, and its share of it
+        let weights = [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ];
+
+        for dy in 0..=sprite.height() as i32 {
+            for dx in 0..=sprite.width() as i32 {
+                let (mut r, mut g, mut b, mut alpha) = (0.0, 0.0, 0.0, 0.0);
+                for (ox, oy, weight) in weights {
+                    let (sx, sy) = (dx - ox, dy - oy);
+                    if sx < 0 || sy < 0 || sx >= sprite.width() as i32 || sy >= sprite.height() as i32 {
+                        continue;
+                    }
+                    let color = options.source(sprite, frame, sx as u32, sy as u32);
+                    let coverage = color.a * weight;
+                    r += color.r * coverage;
+                    g += color.g * coverage;
+                    b += color.b * coverage;
+                    alpha += coverage;
+                }
+                if alpha > 0.0 {
+                    let color = FixedColor::rgb(r / alpha, g / alpha, b / alpha);
+                    self.blend_over(x0 + dx, y0 + dy, color, alpha);
+                }
+            }
+        }
+    }
+
     pub fn blend_rgb(&mut self, x: u32, y: u32, color: FixedColor, _phase: f32, b: Blend)  {
         let src = self.get_pixel(x, y);
         let new_color = b.blend(src, color);
@@ -0,0 +1,190 @@
+// RGBA sprites that can live in flash, e.g.
+//
+//     static SNOWMAN: Sprite = Sprite::sheet(8, 8, 4, include_bytes!("snowman.rgba"));
+//
+// A sprite sheet holds its frames side by side, so the raw data is
+// `width * frames` pixels wide and `height` pixels high, 4 bytes per pixel.
+
+use crate::fixedcolor::FixedColor;
+
+#[derive(Clone, Copy)]
+pub struct Sprite<'a> {
+    width: u32,
+    height: u32,
+    frames: u32,
+    data: &'a [u8],
+}
+
+impl<'a> Sprite<'a> {
+    pub const fn new(width: u32, height: u32, data: &'a [u8]) -> Self {
+        Self::sheet(width, height, 1, data)
+    }
+
+    pub const fn sheet(width: u32, height: u32, frames: u32, data: &'a [u8]) -> Self {
+        assert!(frames > 0);
+        assert!(data.len() == (width * height * frames * 4) as usize);
+        Self {
+            width,
+            height,
+            frames,
+            data,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    // The pixel colour with its alpha in `a`. Out of range frames wrap around.
+    pub fn pixel(&self, frame: u32, x: u32, y: u32) -> FixedColor {
+        let frame = frame % self.frames;
+        let index = ((y * self.width * self.frames + frame * self.width + x) * 4) as usize;
+        let p = &self.data[index..index + 4];
+        FixedColor::from_rgba8(p[0], p[1], p[2], p[3])
+    }
+
+    // The frame to show at time `t` when playing the sheet at `fps`
+    pub fn frame_at(&self, t: f32, fps: f32) -> u32 {
+        if t <= 0.0 || fps <= 0.0 {
+            return 0;
+        }
+        (t * fps) as u32 % self.frames
+    }
+}
+
+// Steps through the frames of a sprite sheet at a fixed rate
+pub struct SpriteAnimation {
+    fps: f32,
+    elapsed: f32,
+}
+
+impl SpriteAnimation {
+    pub fn new(fps: f32) -> Self {
+        Self { fps, elapsed: 0.0 }
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        self.elapsed += dt;
+    }
+
+    pub fn frame(&self, sprite: &Sprite) -> u32 {
+        sprite.frame_at(self.elapsed, self.fps)
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct BlitOptions {
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // Multiplied into every pixel of the sprite
+    pub tint: Option<FixedColor>,
+}
+
+impl BlitOptions {
+    pub(crate) fn source(&self, sprite: &Sprite, frame: u32, x: u32, y: u32) -> FixedColor {
+        let sx = if self.flip_x { sprite.width() - 1 - x } else { x };
+        let sy = if self.flip_y { sprite.height() - 1 - y } else { y };
+        let color = sprite.pixel(frame, sx, sy);
+        match self.tint {
+            Some(tint) => color.multiply(tint),
+            None => color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RenderBuffer;
+
+    // Two 2x1 frames: [red, transparent] [green, half blue]
+    const SHEET: [u8; 16] = [
+        255, 0, 0, 255, 0, 0, 0, 0, 0, 255, 0, 255, 0, 0, 255, 128,
+    ];
+
+    type Buffer = RenderBuffer<{ 4 * 4 }, 4, 4>;
+
+    #[test]
+    fn test_sheet_frames() {
+        let sprite = Sprite::sheet(2, 1, 2, &SHEET);
+        assert_eq!(sprite.pixel(0, 0, 0).as_rgb8(), (255, 0, 0));
+        assert_eq!(sprite.pixel(1, 0, 0).as_rgb8(), (0, 255, 0));
+        assert_eq!(sprite.frame_at(0.0, 10.0), 0);
+        assert_eq!(sprite.frame_at(0.15, 10.0), 1);
+        assert_eq!(sprite.frame_at(0.25, 10.0), 0);
+
+        let mut animation = SpriteAnimation::new(10.0);
+        animation.step(0.15);
+        assert_eq!(animation.frame(&sprite), 1);
+    }
+
+    #[test]
+    fn test_blit_alpha_and_clip() {
+        let sprite = Sprite::sheet(2, 1, 2, &SHEET);
+        let mut buffer = Buffer::new();
+        buffer.clear_to_color(FixedColor::WHITE);
+
+        buffer.blit(&sprite, 0, 3, 0, &BlitOptions::default());
+        assert_eq!(buffer.get_pixel(3, 0).as_rgb8(), (255, 0, 0));
+
+        // The transparent pixel leaves the background alone
+        buffer.blit(&sprite, 0, -1, 1, &BlitOptions::default());
+        assert_eq!(buffer.get_pixel(0, 1).as_rgb8(), (255, 255, 255));
+
+        buffer.blit(&sprite, 1, 0, 2, &BlitOptions::default());
+        let (r, _, b) = buffer.get_pixel(1, 2).as_rgb8();
+        assert!(r > 120 && r < 135);
+        assert_eq!(b, 255);
+    }
+
+    #[test]
+    fn test_blit_flip_and_tint() {
+        let sprite = Sprite::sheet(2, 1, 2, &SHEET);
+        let mut buffer = Buffer::new();
+        let options = BlitOptions {
+            flip_x: true,
+            tint: Some(FixedColor::rgb(0.5, 1.0, 1.0)),
+            ..Default::default()
+        };
+        buffer.blit(&sprite, 0, 0, 0, &options);
+
+        assert_eq!(buffer.get_pixel(0, 0).as_rgb8(), (0, 0, 0));
+        assert_eq!(buffer.get_pixel(1, 0).as_rgb8(), (127, 0, 0));
+    }
+
+    #[test]
+    fn test_blit_subpixel() {
+        let sprite = Sprite::sheet(2, 1, 2, &SHEET);
+        let mut buffer = Buffer::new();
+        buffer.blit_subpixel(&sprite, 0, 0.5, 0.0, &BlitOptions::default());
+
+        // Half of the red pixel lands on each neighbour
+        assert_eq!(buffer.get_pixel(0, 0).as_rgb8(), (127, 0, 0));
+        assert_eq!(buffer.get_pixel(1, 0).as_rgb8(), (127, 0, 0));
+        assert_eq!(buffer.get_pixel(2, 0).as_rgb8(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_blit_subpixel_opaque() {
+        // Red then blue, both opaque
+        const OPAQUE: [u8; 8] = [255, 0, 0, 255, 0, 0, 255, 255];
+        let sprite = Sprite::new(2, 1, &OPAQUE);
+        let mut buffer = Buffer::new();
+        buffer.clear_to_color(FixedColor::WHITE);
+        buffer.blit_subpixel(&sprite, 0, 0.5, 0.0, &BlitOptions::default());
+
+        // The pixel between the two is covered in full, no white shows through
+        assert_eq!(buffer.get_pixel(1, 0).as_rgb8(), (127, 0, 127));
+        // The edges are half covered
+        assert_eq!(buffer.get_pixel(0, 0).as_rgb8(), (255, 127, 127));
+        assert_eq!(buffer.get_pixel(2, 0).as_rgb8(), (127, 127, 255));
+    }
+}
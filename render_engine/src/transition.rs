@@ -1,19 +1,67 @@
 use core::ops::{AddAssign, Div};
 
-use crate::Renderer;
+use crate::renderbuffer::blend_merge;
+use crate::{RenderBuffer, Renderer};
 
+// How one effect gives way to the next
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum TransitionStyle {
+    // Switch when the duration is up
+    Cut,
+    #[default]
+    Crossfade,
+    // Fade out to black, then fade the new effect in
+    FadeThroughBlack,
+    // The new effect sweeps in from the left
+    Wipe,
+}
+
+impl TransitionStyle {
+    // Mix the outgoing frame into the incoming one, progress runs 0.0 to 1.0.
+    // The outgoing frame is left as it is, so it can be held while it fades.
+    pub fn mix<const S: usize, const X: usize, const Y: usize>(&self, progress: f32, outgoing: &RenderBuffer<S, X, Y>, incoming: &mut RenderBuffer<S, X, Y>) {
+        let pixels = incoming.buffer_mut().iter_mut().zip(outgoing.buffer().iter());
+        match self {
+            TransitionStyle::Cut => {
+                for (inc, out) in pixels {
+                    *inc = *out;
+                }
+            }
+            TransitionStyle::Crossfade => {
+                for (inc, out) in pixels {
+                    *inc = blend_merge(*out, *inc, progress);
+                }
+            }
+            TransitionStyle::FadeThroughBlack => {
+                for (inc, out) in pixels {
+                    *inc = if progress < 0.5 { out.scale(1.0 - 2.0 * progress) } else { inc.scale(2.0 * progress - 1.0) };
+                }
+            }
+            TransitionStyle::Wipe => {
+                let edge = (progress * X as f32) as usize;
+                for (index, (inc, out)) in pixels.enumerate() {
+                    if index % X >= edge {
+                        *inc = *out;
+                    }
+                }
+            }
+        }
+    }
+}
 
 // TODO: This should be generic for the duration
 pub struct Transition<T>{
     pub renderer: Renderer,
+    pub style: TransitionStyle,
     duration: T,
     current: T,
 }
 
 impl<T: AddAssign + PartialOrd + Div<Output = T> + Default + Copy> Transition<T> {
-    pub fn new(renderer: Renderer, duration: T) -> Self {
+    pub fn new(renderer: Renderer, duration: T, style: TransitionStyle) -> Self {
         Self {
             renderer,
+            style,
             duration,
             current: T::default(),
         }
@@ -34,4 +82,38 @@ impl<T: AddAssign + PartialOrd + Div<Output = T> + Default + Copy> Transition<T>
             self.current / self.duration
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixedcolor::FixedColor;
+
+    type Buffer = RenderBuffer<{ 4 * 2 }, 4, 2>;
+
+    fn mixed(style: TransitionStyle, progress: f32) -> Buffer {
+        let mut outgoing = Buffer::new();
+        outgoing.clear_to_color(FixedColor::from_rgb8(200, 0, 0));
+        let mut incoming = Buffer::new();
+        incoming.clear_to_color(FixedColor::from_rgb8(0, 0, 200));
+        style.mix(progress, &outgoing, &mut incoming);
+        incoming
+    }
+
+    #[test]
+    fn test_styles() {
+        assert_eq!(mixed(TransitionStyle::Cut, 0.9).get_pixel(0, 0).as_rgb8(), (200, 0, 0));
+
+        let (r, _, b) = mixed(TransitionStyle::Crossfade, 0.5).get_pixel(0, 0).as_rgb8();
+        assert!((99..=101).contains(&r) && (99..=101).contains(&b), "{} {}", r, b);
+
+        let (r, _, b) = mixed(TransitionStyle::FadeThroughBlack, 0.25).get_pixel(0, 0).as_rgb8();
+        assert!((99..=101).contains(&r) && b == 0, "{} {}", r, b);
+        let (r, _, b) = mixed(TransitionStyle::FadeThroughBlack, 0.75).get_pixel(0, 0).as_rgb8();
+        assert!(r == 0 && (99..=101).contains(&b), "{} {}", r, b);
+
+        let wipe = mixed(TransitionStyle::Wipe, 0.5);
+        assert_eq!(wipe.get_pixel(1, 1).as_rgb8(), (0, 0, 200));
+        assert_eq!(wipe.get_pixel(2, 1).as_rgb8(), (200, 0, 0));
+    }
+}
@@ -0,0 +1,199 @@
+// Maps effect coordinates onto the buffer, so an effect can be scaled,
+// rotated, moved or tiled without knowing about it.
+//
+// The transform is applied about the centre of the buffer, and pixels are
+// pulled from the effect's buffer with bilinear filtering.
+
+use crate::fixedcolor::FixedColor;
+use crate::renderbuffer::blend_merge;
+use crate::RenderBuffer;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Wrap {
+    // Outside the effect is black
+    Clip,
+    // The effect repeats in every direction
+    Tile,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    pub scale_x: f32,
+    pub scale_y: f32,
+    // In turns, clockwise
+    pub rotation: f32,
+    pub translate_x: f32,
+    pub translate_y: f32,
+    pub wrap: Wrap,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        scale_x: 1.0,
+        scale_y: 1.0,
+        rotation: 0.0,
+        translate_x: 0.0,
+        translate_y: 0.0,
+        wrap: Wrap::Clip,
+    };
+
+    pub fn scaled(self, scale_x: f32, scale_y: f32) -> Self {
+        Self { scale_x, scale_y, ..self }
+    }
+
+    pub fn rotated(self, rotation: f32) -> Self {
+        Self { rotation, ..self }
+    }
+
+    pub fn translated(self, translate_x: f32, translate_y: f32) -> Self {
+        Self { translate_x, translate_y, ..self }
+    }
+
+    pub fn wrapped(self, wrap: Wrap) -> Self {
+        Self { wrap, ..self }
+    }
+
+    // Resample `src` into `dst` through this transform. The buffers can be
+    // different sizes, e.g. to show an effect made for 50x24 on a 5x24 strip.
+    pub fn apply<const S1: usize, const X1: usize, const Y1: usize, const S2: usize, const X2: usize, const Y2: usize>(
+        &self,
+        src: &RenderBuffer<S1, X1, Y1>,
+        dst: &mut RenderBuffer<S2, X2, Y2>,
+    ) {
+        let angle = -self.rotation * 2.0 * core::f32::consts::PI;
+        let (sin, cos) = (libm::sinf(angle), libm::cosf(angle));
+        let scale_x = if self.scale_x == 0.0 { 1.0 } else { self.scale_x };
+        let scale_y = if self.scale_y == 0.0 { 1.0 } else { self.scale_y };
+
+        for y in 0..Y2 {
+            for x in 0..X2 {
+                // Undo the transform about the centre of each buffer
+                let dx = x as f32 + 0.5 - X2 as f32 / 2.0 - self.translate_x;
+                let dy = y as f32 + 0.5 - Y2 as f32 / 2.0 - self.translate_y;
+                let rx = dx * cos - dy * sin;
+                let ry = dx * sin + dy * cos;
+                let sx = rx / scale_x + X1 as f32 / 2.0 - 0.5;
+                let sy = ry / scale_y + Y1 as f32 / 2.0 - 0.5;
+
+                dst.safe_set_pixel(x as u32, y as u32, self.sample(src, sx, sy));
+            }
+        }
+    }
+
+    fn sample<const S: usize, const X: usize, const Y: usize>(&self, src: &RenderBuffer<S, X, Y>, x: f32, y: f32) -> FixedColor {
+        let x0 = libm::floorf(x);
+        let y0 = libm::floorf(y);
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let top = blend_merge(self.texel(src, x0, y0), self.texel(src, x0 + 1, y0), fx);
+        let bottom = blend_merge(self.texel(src, x0, y0 + 1), self.texel(src, x0 + 1, y0 + 1), fx);
+        blend_merge(top, bottom, fy)
+    }
+
+    fn texel<const S: usize, const X: usize, const Y: usize>(&self, src: &RenderBuffer<S, X, Y>, x: i32, y: i32) -> FixedColor {
+        match self.wrap {
+            Wrap::Tile => src.get_pixel(x.rem_euclid(X as i32) as u32, y.rem_euclid(Y as i32) as u32),
+            Wrap::Clip if x >= 0 && y >= 0 && x < X as i32 && y < Y as i32 => src.get_pixel(x as u32, y as u32),
+            Wrap::Clip => FixedColor::BLACK,
+        }
+    }
+}
+
+// How the transform changes every frame
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TransformAnimation {
+    // Turns per frame
+    pub spin: f32,
+    // Pixels per frame
+    pub drift_x: f32,
+    pub drift_y: f32,
+    // Change in scale per frame, in both directions
+    pub zoom: f32,
+}
+
+impl TransformAnimation {
+    pub fn step(&self, transform: &mut Transform) {
+        transform.rotation = (transform.rotation + self.spin) % 1.0;
+        transform.translate_x += self.drift_x;
+        transform.translate_y += self.drift_y;
+        transform.scale_x += self.zoom;
+        transform.scale_y += self.zoom;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Buffer = RenderBuffer<{ 4 * 4 }, 4, 4>;
+
+    fn lit(b: &Buffer, x: u32, y: u32) -> bool {
+        b.get_pixel(x, y).as_rgb8() != (0, 0, 0)
+    }
+
+    #[test]
+    fn test_identity() {
+        let mut src = Buffer::new();
+        let mut dst = Buffer::new();
+        src.safe_set_pixel(1, 2, FixedColor::RED);
+        Transform::IDENTITY.apply(&src, &mut dst);
+
+        assert_eq!(dst.get_pixel(1, 2).as_rgb8(), (255, 0, 0));
+        assert!(!lit(&dst, 2, 1));
+    }
+
+    #[test]
+    fn test_half_turn() {
+        let mut src = Buffer::new();
+        let mut dst = Buffer::new();
+        src.safe_set_pixel(0, 0, FixedColor::WHITE);
+        Transform::IDENTITY.rotated(0.5).apply(&src, &mut dst);
+
+        assert!(lit(&dst, 3, 3));
+        assert!(!lit(&dst, 0, 0));
+    }
+
+    #[test]
+    fn test_tile_translation() {
+        let mut src = Buffer::new();
+        let mut dst = Buffer::new();
+        src.safe_set_pixel(0, 0, FixedColor::WHITE);
+        Transform::IDENTITY.translated(-1.0, 0.0).wrapped(Wrap::Tile).apply(&src, &mut dst);
+
+        assert!(lit(&dst, 3, 0));
+        assert!(!lit(&dst, 0, 0));
+    }
+
+    #[test]
+    fn test_magnify_onto_narrow_buffer() {
+        type Wide = RenderBuffer<{ 8 * 4 }, 8, 4>;
+        type Narrow = RenderBuffer<{ 2 * 4 }, 2, 4>;
+        let mut src = Wide::new();
+        let mut dst = Narrow::new();
+        // The middle of the wide buffer, either side of the centre line
+        src.safe_set_pixel(3, 1, FixedColor::RED);
+        src.safe_set_pixel(4, 1, FixedColor::GREEN);
+        Transform::IDENTITY.apply(&src, &mut dst);
+
+        assert_eq!(dst.get_pixel(0, 1).as_rgb8(), (255, 0, 0));
+        assert_eq!(dst.get_pixel(1, 1).as_rgb8(), (0, 255, 0));
+    }
+
+    #[test]
+    fn test_animation_step() {
+        let mut transform = Transform::IDENTITY;
+        let animation = TransformAnimation { spin: 0.75, drift_x: 1.0, ..Default::default() };
+        animation.step(&mut transform);
+        animation.step(&mut transform);
+
+        assert!((transform.rotation - 0.5).abs() < 0.001);
+        assert_eq!(transform.translate_x, 2.0);
+    }
+}
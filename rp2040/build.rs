@@ -12,6 +12,7 @@ use std::env;
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
+use std::process::Command;
 
 fn main() {
     // Put `memory.x` in our output directory and ensure it's
@@ -33,4 +34,47 @@ fn main() {
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
     println!("cargo:rustc-link-arg-bins=-Tlink-rp.x");
     println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
+
+    // Reported to the host in the device capabilities. HEAD only changes on a
+    // checkout, so the branch it points at is watched for commits, and the
+    // index for changes that make the build dirty.
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/index");
+    if let Some(branch) = std::fs::read_to_string("../.git/HEAD")
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|branch| branch.trim().to_string()))
+    {
+        // A packed ref has no file of its own until the next commit writes one
+        let branch = format!("../.git/{}", branch);
+        if std::path::Path::new(&branch).exists() {
+            println!("cargo:rerun-if-changed={}", branch);
+        } else {
+            println!("cargo:rerun-if-changed=../.git/packed-refs");
+        }
+    }
+    let commit = Command::new("git")
+        .args(["describe", "--always", "--dirty"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=BUILD_ID={} {} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"), commit);
+
+    // Each controller is built with its own id and the groups it belongs to,
+    // e.g. DEVICE_ID=10 DEVICE_GROUPS=1,2 cargo build. Device 1 in group 1 if unset.
+    println!("cargo:rerun-if-env-changed=DEVICE_ID");
+    println!("cargo:rerun-if-env-changed=DEVICE_GROUPS");
+    let id = |id: &str| id.trim().parse::<u16>().unwrap_or_else(|e| panic!("bad id {:?}: {}", id, e));
+    let device = env::var("DEVICE_ID").map_or(1, |device| id(&device));
+    let groups: Vec<u16> = match env::var("DEVICE_GROUPS") {
+        Ok(groups) => groups.split(',').filter(|group| !group.trim().is_empty()).map(id).collect(),
+        Err(_) => vec![1],
+    };
+    // command::MAX_GROUPS
+    assert!(groups.len() <= 8, "a device can be in at most 8 groups");
+    File::create(out.join("identity.rs"))
+        .unwrap()
+        .write_all(format!("const DEVICE_ID: u16 = {};\nconst GROUPS: [u16; {}] = {:?};\n", device, groups.len(), groups).as_bytes())
+        .unwrap();
 }
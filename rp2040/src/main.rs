@@ -17,7 +17,7 @@ use static_cell::StaticCell;
 use core::cell::RefCell;
 //use wifi::init_wifi;
 //use crate::statusled::status_led;
-use crate::renderer::render_engine;
+use crate::renderer::{present_frames, render_engine};
 
 use {defmt_rtt as _, panic_probe as _};
 
@@ -48,6 +48,7 @@ async fn main(spawner: Spawner) {
     //spawner.spawn(status_led(p.PIN_25.into())).unwrap();
     spawner.spawn(led_strip_control(p.PIO0, p.DMA_CH0, p.PIN_16, buffer)).unwrap();
     spawner.spawn(render_engine(engine, buffer)).unwrap();
+    spawner.spawn(present_frames(buffer)).unwrap();
 }
 
  
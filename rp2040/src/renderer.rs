@@ -6,16 +6,27 @@ use embassy_rp::peripherals::{DMA_CH0, PIN_16, PIO0};
 use embassy_rp::pio::Pio;
 use embassy_rp::pio_programs::ws2812::{PioWs2812, PioWs2812Program};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
 use embassy_sync::channel::Channel;
-use embassy_time::{Duration, Ticker, Timer};
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Ticker, Timer};
 
-use render_engine::{RenderBuffer, RenderEngine, Renderer, RenderType};
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use command::{FrameQueue, QueueError};
+
+use render_engine::{marquee_text, AudioFeatures, Date, Input, MarqueeText, Param, RenderBuffer, RenderEngine, Renderer, RenderType, TransitionStyle};
 use smart_leds::RGB;
 
 const LEDS_PER_DROP: usize = 24;
 const NUM_DROPS: usize = 5;
+const FRAME_MILLIS: u64 = 40;
+// Streamed frames held for their presentation time, enough for a few hundred milliseconds of latency
+const QUEUED_FRAMES: usize = 8;
 
 pub type RenderEngine50x24 = RenderEngine<{NUM_DROPS * LEDS_PER_DROP}, NUM_DROPS, LEDS_PER_DROP>;
+#[derive(Clone)]
 pub struct Buffer50x24(RenderBuffer<{NUM_DROPS * LEDS_PER_DROP}, NUM_DROPS, LEDS_PER_DROP>);
 
 impl Buffer50x24 {
@@ -73,21 +84,151 @@ impl Iterator for BufferIterator<'_> {
 }
 
 
+// Every animation the firmware plays, both to pick a renderer and to list in
+// the capabilities. Elementary stands for every rule.
+const ANIMATIONS: [(command::Animation, Renderer); 20] = [
+    (command::Animation::None, Renderer::None),
+    (command::Animation::Snow, Renderer::Basic(RenderType::Snow)),
+    (command::Animation::Sparkle, Renderer::Basic(RenderType::Sparkle)),
+    (command::Animation::Rainbow, Renderer::Basic(RenderType::Rainbow)),
+    (command::Animation::Marquee, Renderer::Basic(RenderType::Marquee)),
+    (command::Animation::Fire, Renderer::Basic(RenderType::Fire)),
+    (command::Animation::Fireworks, Renderer::Basic(RenderType::Fireworks)),
+    (command::Animation::Life, Renderer::Basic(RenderType::Life)),
+    (command::Animation::Elementary(0), Renderer::Basic(RenderType::Elementary(0))),
+    (command::Animation::Plasma, Renderer::Basic(RenderType::Plasma)),
+    (command::Animation::Aurora, Renderer::Basic(RenderType::Aurora)),
+    (command::Animation::Icicle, Renderer::Basic(RenderType::Icicle)),
+    (command::Animation::Matrix, Renderer::Basic(RenderType::Matrix)),
+    (command::Animation::Countdown, Renderer::Basic(RenderType::Countdown)),
+    (command::Animation::Snake, Renderer::Basic(RenderType::Snake)),
+    (command::Animation::Pong, Renderer::Basic(RenderType::Pong)),
+    (command::Animation::VuMeter, Renderer::Basic(RenderType::VuMeter)),
+    (command::Animation::BeatFlash, Renderer::Basic(RenderType::BeatFlash)),
+    (command::Animation::Waterfall, Renderer::Basic(RenderType::Waterfall)),
+    (command::Animation::Pulse, Renderer::Basic(RenderType::Pulse)),
+];
+
+// Anything missing from ANIMATIONS shows nothing, as it isn't listed either
 pub fn get_renderer_for(command: command::Animation) -> Renderer {
-    match command {
-        command::Animation::None => Renderer::None,
-        command::Animation::Snow => Renderer::Basic(RenderType::Snow),
-        command::Animation::Sparkle => Renderer::Basic(RenderType::Sparkle),
-        command::Animation::Rainbow => Renderer::Basic(RenderType::Rainbow),
+    if let command::Animation::Elementary(rule) = command {
+        return Renderer::Basic(RenderType::Elementary(rule));
+    }
+    ANIMATIONS
+        .iter()
+        .find(|(animation, _)| *animation == command)
+        .map_or(Renderer::None, |(_, renderer)| *renderer)
+}
+
+pub fn capabilities(identity: command::Identity) -> command::Capabilities {
+    command::Capabilities {
+        width: NUM_DROPS as u16,
+        height: LEDS_PER_DROP as u16,
+        // Each drop is a strip hanging from the top, one after another
+        layout: command::Layout::Columns,
+        animations: ANIMATIONS.iter().map(|(animation, _)| *animation).collect(),
+        params: [command::ParamKind::Speed, command::ParamKind::FireCooling, command::ParamKind::FireSparking].into_iter().collect(),
+        fps: (1000 / FRAME_MILLIS) as u16,
+        protocol_version: command::PROTOCOL_VERSION,
+        firmware: command::Text::try_from(env!("BUILD_ID")).unwrap_or_default(),
+        device: identity.device,
+        groups: identity.groups,
+    }
+}
+
+// Both sides hold up to the same MAX_BANDS in a heapless Vec, so the bands move straight across
+pub fn get_audio_for(frame: command::AudioFrame) -> AudioFeatures {
+    AudioFeatures { bands: frame.bands, level: frame.level, beat: frame.beat }
+}
+
+pub fn get_transition_style_for(style: command::TransitionStyle) -> TransitionStyle {
+    match style {
+        command::TransitionStyle::Cut => TransitionStyle::Cut,
+        command::TransitionStyle::Crossfade => TransitionStyle::Crossfade,
+        command::TransitionStyle::FadeThroughBlack => TransitionStyle::FadeThroughBlack,
+        command::TransitionStyle::Wipe => TransitionStyle::Wipe,
+    }
+}
+
+pub fn get_param_for(param: command::Param) -> Param {
+    match param {
+        command::Param::Speed(speed) => Param::Speed(speed),
+        command::Param::FireCooling(cooling) => Param::FireCooling(cooling),
+        command::Param::FireSparking(sparking) => Param::FireSparking(sparking),
+    }
+}
+
+pub fn get_input_for(input: command::Input) -> Input {
+    match input {
+        command::Input::Up => Input::Up,
+        command::Input::Down => Input::Down,
+        command::Input::Left => Input::Left,
+        command::Input::Right => Input::Right,
+        command::Input::Button => Input::Button,
     }
 }
 
+pub enum EngineControl {
+    SetRenderer(Renderer),
+    SetTransition(Renderer, f32, TransitionStyle),
+    SetParam(Param),
+    SetText(MarqueeText),
+    SetTime(u64),
+    SetCountdownTarget(Option<Date>),
+    Input(u8, Input),
+    Audio(AudioFeatures),
+    SetBpm(Option<f32>),
+    TapTempo,
+    SyncBeat,
+}
 
-static RENDERENGINE_CONTROL: Channel<CriticalSectionRawMutex, Renderer, 2> = Channel::new();
+static RENDERENGINE_CONTROL: Channel<CriticalSectionRawMutex, EngineControl, 2> = Channel::new();
 
 pub async fn set_renderer(renderer: Renderer) {
     defmt::info!("Sending renderer control message");
-    RENDERENGINE_CONTROL.send(renderer).await;
+    RENDERENGINE_CONTROL.send(EngineControl::SetRenderer(renderer)).await;
+}
+
+pub async fn set_transition(renderer: Renderer, duration: f32, style: TransitionStyle) {
+    RENDERENGINE_CONTROL.send(EngineControl::SetTransition(renderer, duration, style)).await;
+}
+
+pub async fn set_param(param: Param) {
+    RENDERENGINE_CONTROL.send(EngineControl::SetParam(param)).await;
+}
+
+pub async fn set_text(text: &str) {
+    RENDERENGINE_CONTROL.send(EngineControl::SetText(marquee_text(text))).await;
+}
+
+pub async fn set_time(seconds: u64) {
+    RENDERENGINE_CONTROL.send(EngineControl::SetTime(seconds)).await;
+}
+
+pub async fn set_countdown_target(target: Option<Date>) {
+    RENDERENGINE_CONTROL.send(EngineControl::SetCountdownTarget(target)).await;
+}
+
+pub async fn send_input(player: u8, input: Input) {
+    RENDERENGINE_CONTROL.send(EngineControl::Input(player, input)).await;
+}
+
+// Audio frames are stale by the time the engine catches up, so rather than
+// wait they are dropped when it is behind. False if this one was.
+pub fn set_audio(features: AudioFeatures) -> bool {
+    RENDERENGINE_CONTROL.try_send(EngineControl::Audio(features)).is_ok()
+}
+
+pub async fn set_bpm(bpm: Option<f32>) {
+    RENDERENGINE_CONTROL.send(EngineControl::SetBpm(bpm)).await;
+}
+
+pub async fn tap_tempo() {
+    RENDERENGINE_CONTROL.send(EngineControl::TapTempo).await;
+}
+
+pub async fn sync_beat() {
+    RENDERENGINE_CONTROL.send(EngineControl::SyncBeat).await;
 }
 
 #[embassy_executor::task]
@@ -97,12 +238,13 @@ pub async fn render_engine(engine: &'static SharedEngine, buffer: &'static Share
         engine.borrow_mut().set_renderer(Renderer::Basic(RenderType::Snow));
     });
 
-    let mut ticker = Ticker::every(Duration::from_millis(40));
+    let mut ticker = Ticker::every(Duration::from_millis(FRAME_MILLIS));
     let mut paused = false;
+    let mut last_frame = Instant::now();
 
     loop {
         match select(RENDERENGINE_CONTROL.receive(), ticker.next()).await {
-            Either::First(r) => { // The control channel has received a message
+            Either::First(EngineControl::SetRenderer(r)) => { // The control channel has received a message
                 defmt::info!("Received renderer control message");
                 engine.lock(|engine| {
                     engine.borrow_mut().set_renderer(r);
@@ -111,13 +253,88 @@ pub async fn render_engine(engine: &'static SharedEngine, buffer: &'static Share
                 paused = r == Renderer::None;
             }
 
+            Either::First(EngineControl::SetTransition(r, duration, style)) => {
+                defmt::info!("Received transition control message");
+                engine.lock(|engine| {
+                    engine.borrow_mut().set_transition_with_style(r, duration, style);
+                });
+                // Keep rendering until the transition is over, even to None
+                paused = false;
+            }
+
+            Either::First(EngineControl::SetParam(param)) => {
+                engine.lock(|engine| {
+                    engine.borrow_mut().set_param(param);
+                });
+            }
+
+            Either::First(EngineControl::SetText(text)) => {
+                defmt::info!("Received marquee text");
+                engine.lock(|engine| {
+                    engine.borrow_mut().set_text(&text);
+                });
+            }
+
+            Either::First(EngineControl::SetTime(seconds)) => {
+                defmt::info!("Received time {}", seconds);
+                engine.lock(|engine| {
+                    engine.borrow_mut().set_time(seconds);
+                });
+            }
+
+            Either::First(EngineControl::SetCountdownTarget(target)) => {
+                defmt::info!("Received countdown target");
+                engine.lock(|engine| {
+                    engine.borrow_mut().set_countdown_target(target);
+                });
+            }
+
+            Either::First(EngineControl::Input(player, input)) => {
+                engine.lock(|engine| {
+                    engine.borrow_mut().input(player, input);
+                });
+            }
+
+            Either::First(EngineControl::Audio(features)) => {
+                engine.lock(|engine| {
+                    engine.borrow_mut().set_audio(&features);
+                });
+            }
+
+            Either::First(EngineControl::SetBpm(bpm)) => {
+                engine.lock(|engine| {
+                    engine.borrow_mut().set_bpm(bpm);
+                });
+            }
+
+            Either::First(EngineControl::TapTempo) => {
+                engine.lock(|engine| {
+                    engine.borrow_mut().tap_tempo();
+                });
+            }
+
+            Either::First(EngineControl::SyncBeat) => {
+                engine.lock(|engine| {
+                    engine.borrow_mut().sync_beat();
+                });
+            }
+
             Either::Second(_) => { // The timer has expired
-                if !paused {
+                // The wall clock keeps time even while paused
+                let dt = last_frame.elapsed().as_micros() as f32 / 1_000_000.0;
+                last_frame = Instant::now();
+                if paused {
+                    engine.lock(|engine| {
+                        engine.borrow_mut().step_clock(dt);
+                    });
+                } else {
                     // Get access to the shared render buffer
                     buffer.lock(|buffer| {
                         let mut b = buffer.borrow_mut();
                         engine.lock(|engine| {
-                            engine.borrow_mut().render(0.0, 0.0, b.get_mut_buffer());
+                            let mut engine = engine.borrow_mut();
+                            engine.render(0.0, dt, b.get_mut_buffer());
+                            paused = engine.get_renderer() == Renderer::None && !engine.is_transitioning();
                         });
                     });
                 
@@ -132,11 +349,50 @@ pub async fn render_engine(engine: &'static SharedEngine, buffer: &'static Share
 }
 
 static LEDSTRIP: Channel<CriticalSectionRawMutex, (), 2> = Channel::new();
+// Applied as the LEDs are written, so it dims frames from the host as well as effects
+static BRIGHTNESS: AtomicU8 = AtomicU8::new(255);
+
+pub fn set_brightness(brightness: u8) {
+    BRIGHTNESS.store(brightness, Ordering::Relaxed);
+}
 
 pub async fn flush_led_strip() {
     LEDSTRIP.send(()).await;
 }
 
+static FRAME_QUEUE: Mutex<CriticalSectionRawMutex, RefCell<FrameQueue<Buffer50x24, QUEUED_FRAMES>>> = Mutex::new(RefCell::new(FrameQueue::new()));
+static FRAME_QUEUED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+// Show a copy of the frame at `due` milliseconds on the device clock
+pub fn queue_frame(due: u64, frame: &Buffer50x24) -> Result<(), QueueError> {
+    let result = FRAME_QUEUE.lock(|queue| {
+        queue.borrow_mut().push(due, frame.clone(), Instant::now().as_millis())
+    });
+    FRAME_QUEUED.signal(());
+    result
+}
+
+#[embassy_executor::task]
+pub async fn present_frames(buffer: &'static SharedBuffer) {
+    loop {
+        // Wake when the next frame is due, or when one is queued that may be due sooner
+        match FRAME_QUEUE.lock(|queue| queue.borrow().next_due()) {
+            Some(due) => {
+                select(Timer::at(Instant::from_millis(due)), FRAME_QUEUED.wait()).await;
+            }
+            None => FRAME_QUEUED.wait().await,
+        }
+
+        let frame = FRAME_QUEUE.lock(|queue| queue.borrow_mut().pop_due(Instant::now().as_millis()));
+        if let Some(frame) = frame {
+            buffer.lock(|buffer| {
+                *buffer.borrow_mut() = frame;
+            });
+            flush_led_strip().await;
+        }
+    }
+}
+
 #[embassy_executor::task]
 pub async fn led_strip_control(pio: PIO0, dma: DMA_CH0, pin: PIN_16, buffer: &'static SharedBuffer) {
     let Pio { mut common, sm0, .. } = Pio::new(pio, Irqs);
@@ -149,7 +405,7 @@ pub async fn led_strip_control(pio: PIO0, dma: DMA_CH0, pin: PIN_16, buffer: &'s
 
         buffer.lock(|buffer| {
             let b = buffer.borrow();
-            ws2812.write_iter(b.into_iter());
+            ws2812.write_iter(smart_leds::brightness(b.into_iter(), BRIGHTNESS.load(Ordering::Relaxed)));
         });
 
         ws2812.flush().await;
@@ -1,4 +1,4 @@
-use crate::renderer::{flush_led_strip, get_renderer_for, set_renderer};
+use crate::renderer::{capabilities, flush_led_strip, queue_frame, Buffer50x24, get_audio_for, get_input_for, get_param_for, get_renderer_for, get_transition_style_for, send_input, set_audio, set_bpm, set_brightness, set_countdown_target, set_param, set_renderer, set_text, set_time, set_transition, sync_beat, tap_tempo};
 use crate::{Irqs, SharedBuffer};
 
 use defmt::*;
@@ -12,16 +12,19 @@ use embassy_rp::gpio::{AnyPin, Level, Output};
 use embassy_rp::clocks::RoscRng;
 use embassy_rp::peripherals::{DMA_CH1, PIN_24, PIN_29, PIO1};
 use embassy_rp::pio::Pio;
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
 
 use rand::RngCore;
 use render_engine::fixedcolor::FixedColor;
+use render_engine::Date;
 use static_cell::StaticCell;
-use command::Command;
-use serde::de::Deserialize;
+use command::{ClockSync, Command, Identity, DecodeError, Decoder, ErrorCode, FrameHeader, FrameSync, QueueError, Reply, Response, SyncError, MAX_ENVELOPE_LEN, MAX_RESPONSE_LEN};
+use embedded_io_async::Write;
 
 const WIFI_NETWORK: &str = "18mlf";
 const WIFI_PASSWORD: &str = "eieioitsofftoworkwego";
+// DEVICE_ID and GROUPS, unique to each controller and set at build time
+include!(concat!(env!("OUT_DIR"), "/identity.rs"));
 
 #[embassy_executor::task]
 async fn cyw43_task(runner: cyw43::Runner<'static, Output<'static>, PioSpi<'static, PIO1, 0, DMA_CH1>>) -> ! {
@@ -61,7 +64,7 @@ async fn io_task(stack: Stack<'static> , mut control: Control<'static>, buffer:
 
     let mut rx_buffer = [0; 4096];
     let mut tx_buffer = [0; 4096];
-    let mut buf = [0; 4096];
+    let mut decoder = Decoder::<MAX_ENVELOPE_LEN>::new().with_identity(identity());
 
 
     loop {
@@ -78,12 +81,12 @@ async fn io_task(stack: Stack<'static> , mut control: Control<'static>, buffer:
         info!("Received connection from {:?}", socket.remote_endpoint());
         control.gpio_set(0, true).await;
 
-
-        let mut write_offset=0;
+        // A new connection starts from a keyframe, and a new clock sync
+        let mut stream = Stream::new();
+        decoder.clear();
 
         loop {
-            info!("Reading data into buffer at offset {}", write_offset);
-            let n = match socket.read(&mut buf[write_offset..]).await {
+            let n = match socket.read(decoder.space()).await {
                 Ok(0) => {
                     warn!("read EOF");
                     break;
@@ -94,55 +97,123 @@ async fn io_task(stack: Stack<'static> , mut control: Control<'static>, buffer:
                     break;
                 }
             };
-            let buffer_end = write_offset + n;
-
-            info!("Received data {} buffer end {}", n, buffer_end);
-
-            let mut de = minicbor_serde::Deserializer::new(&mut buf);
-
-            // let c = Command::deserialize(&mut de);
-            // let mut c = Command::Flush;
-            // Command::deserialize_in_place(&mut de, &mut c);
-            // info!("decoder position ",  de.decoder().position());
-
-            let mut last_decoded_position = 0;
-
-            while de.decoder().position() < buffer_end {
-                let c = Command::deserialize(&mut de);
-                match c {
-                    Ok(command) => {
-                        process_command(command, buffer).await;
-                        last_decoded_position = de.decoder().position();
+            decoder.filled(n);
+
+            while let Some(result) = decoder.decode() {
+                let (id, response) = match result {
+                    Ok(request) => (request.id, process_command(request.command, buffer, &mut stream).await),
+                    Err(DecodeError::Version(version)) => {
+                        warn!("Peer speaks protocol version {}, this is version {}", version, command::PROTOCOL_VERSION);
+                        (None, Response::Unsupported)
                     }
-                    Err(_)  => {
-                        // There may be unprocessed data, so we need to break out of the loop
-                        error!("Failed to decode command");
-                        break;
+                    Err(e) => {
+                        warn!("Dropped bytes from the stream: {}", Debug2Format(&e));
+                        (None, response_for(e))
                     }
+                };
+                // Only failures get a reply when the host did not ask for one
+                if id.is_some() || !response.is_ok() {
+                    respond(&mut socket, &Reply { id, response }).await;
                 }
             }
+        }
+    }
+}
+
+// A stream of frames on one connection. Parts are put together here, and a
+// complete frame goes out on the next Flush, or when it is due if it has a
+// presentation time.
+struct Stream {
+    sync: FrameSync,
+    clock: ClockSync,
+    frame: Buffer50x24,
+}
+
+impl Stream {
+    fn new() -> Self {
+        Self {
+            sync: FrameSync::new(),
+            clock: ClockSync::new(),
+            frame: Buffer50x24::new(),
+        }
+    }
+
+    fn completed(&self, header: &FrameHeader, buffer: &'static SharedBuffer) -> Response {
+        if self.sync.sequence() != Some(header.sequence) {
+            return Response::Ok;
+        }
+        let Some(present_at) = header.present_at else {
+            buffer.lock(|buffer| {
+                *buffer.borrow_mut() = self.frame.clone();
+            });
+            return Response::Ok;
+        };
+        let Some(due) = self.clock.to_device(present_at) else {
+            return Response::Error(ErrorCode::NoClock);
+        };
+        match queue_frame(due, &self.frame) {
+            Ok(()) => Response::Ok,
+            Err(QueueError::Late) => {
+                warn!("Frame {} is {}ms late", header.sequence, Instant::now().as_millis().saturating_sub(due));
+                Response::Error(ErrorCode::Late)
+            }
+            Err(QueueError::Full) => Response::Busy,
+        }
+    }
+}
+
+fn identity() -> Identity {
+    Identity { device: DEVICE_ID, groups: GROUPS.into_iter().collect() }
+}
 
-            if last_decoded_position < buffer_end {
-                warn!("Failed to decode with {} bytes left in buffer", buffer_end - last_decoded_position);
-                println!("{:?}", buf[last_decoded_position..buffer_end]);
-                buf.copy_within(last_decoded_position..buffer_end, 0);
-                write_offset = buffer_end - last_decoded_position;
+fn response_for(error: DecodeError) -> Response {
+    match error {
+        DecodeError::Garbage(_) | DecodeError::Corrupt => Response::Error(ErrorCode::Corrupt),
+        DecodeError::TooLarge(_) => Response::Error(ErrorCode::TooLarge),
+        DecodeError::Version(_) | DecodeError::Unknown => Response::Unsupported,
+    }
+}
 
-                // HACK: If we have a partial command, we need to flush the buffer
-                flush_led_strip().await;
-            } else {
-                write_offset = 0;
+async fn respond(socket: &mut TcpSocket<'_>, reply: &Reply) {
+    let mut buf = [0; MAX_RESPONSE_LEN];
+    match command::encode(reply, &mut buf) {
+        Ok(bytes) => {
+            if let Err(e) = socket.write_all(bytes).await {
+                warn!("write error: {:?}", e);
             }
         }
+        Err(e) => warn!("Failed to encode reply: {}", Debug2Format(&e)),
     }
 }
 
-async fn process_command(command: Command, buffer: &'static SharedBuffer) {
+async fn process_command(command: Command, buffer: &'static SharedBuffer, stream: &mut Stream) -> Response {
     match command {
         Command::Animate(anim) => {
             info!("Animate");
             set_renderer(get_renderer_for(anim)).await;
         }
+        Command::AnimateWith { animation, transition_duration, style, params } => {
+            info!("AnimateWith: {} seconds", transition_duration);
+            // NaN would never finish
+            if !transition_duration.is_finite() || params.iter().any(|param| !valid_param(param)) {
+                return Response::Error(ErrorCode::OutOfRange);
+            }
+            for param in params {
+                set_param(get_param_for(param)).await;
+            }
+            set_transition(get_renderer_for(animation), transition_duration, get_transition_style_for(style)).await;
+        }
+        Command::SetParam(param) => {
+            info!("SetParam");
+            if !valid_param(&param) {
+                return Response::Error(ErrorCode::OutOfRange);
+            }
+            set_param(get_param_for(param)).await;
+        }
+        Command::SetBrightness(brightness) => {
+            info!("SetBrightness: {}", brightness);
+            set_brightness(brightness);
+        }
         Command::Clear(r,g,b) => {
             info!("Clear: r={}, g={}, b={}", r, g, b);
             buffer.lock(|buffer| {
@@ -151,20 +222,120 @@ async fn process_command(command: Command, buffer: &'static SharedBuffer) {
         }
         Command::SetPixel(x, y, r, g, b, ) => {
             //info!("SetPixel: x={}, y={}, r={}, g={}, b={}", x, y, r, g, b);
-            buffer.lock(|buffer| {
-                buffer.borrow_mut().get_mut_buffer().safe_set_pixel(x as u32, y as u32, FixedColor::from_rgb8(r, g, b));
+            let inside = buffer.lock(|buffer| {
+                let mut buffer = buffer.borrow_mut();
+                let buffer = buffer.get_mut_buffer();
+                let size = buffer.size();
+                buffer.safe_set_pixel(x as u32, y as u32, FixedColor::from_rgb8(r, g, b));
+                (x as u32) < size.x && (y as u32) < size.y
+            });
+            if !inside {
+                return Response::Error(ErrorCode::OutOfRange);
+            }
+        }
+        Command::SetText(text) => {
+            info!("SetText: {}", text.as_str());
+            set_text(&text).await;
+        }
+        Command::SetTime(seconds) => {
+            info!("SetTime: {}", seconds);
+            set_time(seconds).await;
+        }
+        Command::SetCountdownTarget(year, month, day) => {
+            info!("SetCountdownTarget: {}-{}-{}", year, month, day);
+            let date = Date::new(year as i32, month, day);
+            // Out of range days come back as a different date
+            if Date::from_days(date.to_days()) != date {
+                return Response::Error(ErrorCode::OutOfRange);
+            }
+            set_countdown_target(Some(date)).await;
+        }
+        Command::ClearCountdownTarget => {
+            info!("ClearCountdownTarget");
+            set_countdown_target(None).await;
+        }
+        Command::Input(player, input) => {
+            info!("Input: player {}", player);
+            send_input(player, get_input_for(input)).await;
+        }
+        Command::Audio(frame) => {
+            if !set_audio(get_audio_for(frame)) {
+                return Response::Busy;
+            }
+        }
+        Command::SetFrame(frame) => {
+            let result = buffer.lock(|buffer| {
+                frame.write_to(buffer.borrow_mut().get_mut_buffer())
             });
+            if let Err(e) = result {
+                warn!("SetFrame: bad frame at {},{}: {}", frame.x, frame.y, Debug2Format(&e));
+                return Response::Error(ErrorCode::BadFrame);
+            }
+        }
+        Command::Keyframe(header, frame) => {
+            if let Err(e) = stream.sync.keyframe(&header, &frame, stream.frame.get_mut_buffer()) {
+                warn!("Keyframe {} part {}: {}", header.sequence, header.part, Debug2Format(&e));
+                return response_for_sync(e);
+            }
+            return stream.completed(&header, buffer);
+        }
+        Command::Delta(header, delta) => {
+            if let Err(e) = stream.sync.delta(&header, &delta, stream.frame.get_mut_buffer()) {
+                warn!("Delta {} part {}: {}, waiting for keyframe", header.sequence, header.part, Debug2Format(&e));
+                return response_for_sync(e);
+            }
+            return stream.completed(&header, buffer);
+        }
+        Command::SetBpm(bpm) => {
+            info!("SetBpm: {}", bpm);
+            set_bpm(if bpm > 0.0 { Some(bpm) } else { None }).await;
+        }
+        Command::TapTempo => {
+            info!("TapTempo");
+            tap_tempo().await;
+        }
+        Command::SyncBeat => {
+            info!("SyncBeat");
+            sync_beat().await;
         }
         // Command::SetBuffer(data) => {
         //     buffer.lock(|buffer| {
         //         //buffer.borrow_mut().get_mut_buffer().buffer_mut().copy_from_slice(&data);
         //     });
         // }
+        drawing @ (Command::FillRect(..) | Command::Line(..) | Command::HorizontalGradient(..)
+            | Command::VerticalGradient(..) | Command::Circle(..) | Command::Text(..)) => {
+            buffer.lock(|buffer| {
+                drawing.draw_to(buffer.borrow_mut().get_mut_buffer());
+            });
+        }
+        Command::SyncClock(host) => {
+            stream.clock.sample(host, Instant::now().as_millis());
+        }
+        Command::Query => {
+            info!("Query");
+            return Response::Capabilities(capabilities(identity()));
+        }
         Command::Flush => {
             info!("Flush");
             flush_led_strip().await;
         }
-    }        
+    }
+    Response::Ok
+}
+
+fn valid_param(param: &command::Param) -> bool {
+    match param {
+        command::Param::Speed(speed) => speed.is_finite(),
+        _ => true,
+    }
+}
+
+fn response_for_sync(error: SyncError) -> Response {
+    match error {
+        SyncError::WaitingForKeyframe | SyncError::Missed => Response::Error(ErrorCode::OutOfSync),
+        SyncError::Frame(_) => Response::Error(ErrorCode::BadFrame),
+    }
 }
 
 
@@ -68,17 +68,24 @@ const DELAY: time::Duration = time::Duration::from_millis(1000);
 fn main() {
     println!("Hello, world!");
     let mut buffer= Buffer50x24::new();
-    let mut engine = RenderEngine::new();
+    let mut engine: RenderEngine<NUM_LEDS, NUM_DROPS, LEDS_PER_DROP> = RenderEngine::new();
     let sleep_duration = time::Duration::from_millis(40);
 
     engine.set_renderer(Renderer::Basic(RenderType::Snow));
+    // Follows the system clock, which is UTC unless the Pi is set otherwise
+    if let Ok(now) = time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
+        engine.set_time(now.as_secs());
+    }
 
 
     let mut ws = Ws2812Rpi::new(NUM_LEDS as i32, PIN).unwrap();
 
 
+    let mut last_frame = time::Instant::now();
     loop {
-        engine.render(0.0, 0.0, buffer.get_mut_buffer());
+        let dt = last_frame.elapsed().as_secs_f32();
+        last_frame = time::Instant::now();
+        engine.render(0.0, dt, buffer.get_mut_buffer());
         ws.write(buffer.into_iter());
         // sleep for 40ms
         thread::sleep(sleep_duration);
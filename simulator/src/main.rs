@@ -1,5 +1,5 @@
 use bevy::{prelude::*, render::camera::ScalingMode};
-use render_engine::{RenderBuffer, RenderEngine, Renderer, RenderType};
+use render_engine::{AudioFeatures, Input, MAX_BANDS, PostEffect, RenderBuffer, RenderEngine, Renderer, RenderType, Transform, TransformAnimation, Wrap};
 use az::Cast;
 
 //
@@ -48,9 +48,9 @@ fn main() {
         .add_systems(Startup, (setup, set_default_shader.after(setup)))
         .add_systems(
             FixedUpdate,
-            (update_offscreen_render, update_pixels),
+            (fake_audio, update_offscreen_render, update_pixels).chain(),
         )
-        .add_systems(Update, keyboard_input)
+        .add_systems(Update, (keyboard_input, game_input))
 
         
         .run();
@@ -121,6 +121,12 @@ fn setup(mut commands: Commands, windows: Query<&mut Window>) {
 
 fn set_default_shader(mut r: ResMut<LEDRenderEngine>) {
     r.engine.set_renderer(Renderer::Basic(RenderType::Sparkle));
+    // The same tempo as the made up music, tap with tab to change it
+    r.engine.set_bpm(Some(120.0));
+    // The simulator has no timezone handling, so the countdown runs on UTC
+    if let Ok(now) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        r.engine.set_time(now.as_secs());
+    }
 }
 
 fn keyboard_input(keys: Res<ButtonInput<KeyCode>>, mut r: ResMut<LEDRenderEngine>) {
@@ -128,6 +134,55 @@ fn keyboard_input(keys: Res<ButtonInput<KeyCode>>, mut r: ResMut<LEDRenderEngine
         r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Sparkle), 1.0);
     } else if keys.just_pressed(KeyCode::Digit2) {
         r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Snow), 1.0);
+    } else if keys.just_pressed(KeyCode::Digit3) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Marquee), 1.0);
+    } else if keys.just_pressed(KeyCode::Digit4) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Fire), 1.0);
+    } else if keys.just_pressed(KeyCode::Digit5) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Fireworks), 1.0);
+    } else if keys.just_pressed(KeyCode::Digit6) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Life), 1.0);
+    } else if keys.just_pressed(KeyCode::Digit7) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Elementary(30)), 1.0);
+    } else if keys.just_pressed(KeyCode::Digit8) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Plasma), 1.0);
+    } else if keys.just_pressed(KeyCode::Digit9) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Aurora), 1.0);
+    } else if keys.just_pressed(KeyCode::Digit0) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Icicle), 1.0);
+    } else if keys.just_pressed(KeyCode::KeyM) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Matrix), 1.0);
+    } else if keys.just_pressed(KeyCode::KeyC) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Countdown), 1.0);
+    } else if keys.just_pressed(KeyCode::KeyV) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::VuMeter), 1.0);
+    } else if keys.just_pressed(KeyCode::KeyB) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::BeatFlash), 1.0);
+    } else if keys.just_pressed(KeyCode::KeyF) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Waterfall), 1.0);
+    } else if keys.just_pressed(KeyCode::KeyU) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Pulse), 1.0);
+    } else if keys.just_pressed(KeyCode::Tab) {
+        r.engine.tap_tempo();
+    } else if keys.just_pressed(KeyCode::KeyN) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Snake), 1.0);
+    } else if keys.just_pressed(KeyCode::KeyP) {
+        r.engine.set_transition_to_renderer(Renderer::Basic(RenderType::Pong), 1.0);
+    } else if keys.just_pressed(KeyCode::KeyT) {
+        // Toggle trails behind moving points
+        if r.engine.post_effects().is_empty() {
+            let _ = r.engine.push_post_effect(PostEffect::Decay(0.8));
+        } else {
+            r.engine.clear_post_effects();
+        }
+    } else if keys.just_pressed(KeyCode::KeyR) {
+        // Toggle a slow spin of the current effect
+        if r.engine.get_transform().is_some() {
+            r.engine.clear_transform();
+        } else {
+            let animation = TransformAnimation { spin: 0.002, ..Default::default() };
+            r.engine.set_animated_transform(Transform::IDENTITY.wrapped(Wrap::Tile), animation);
+        }
     // } else if keys.just_pressed(KeyCode::Digit3) {
     //     r.engine.set_transition_to_renderer(Renderer::Shader(Shader::Rainbow), Fixed::from_num(1.0));
     // } else if keys.just_pressed(KeyCode::Digit4) {
@@ -136,6 +191,45 @@ fn keyboard_input(keys: Res<ButtonInput<KeyCode>>, mut r: ResMut<LEDRenderEngine
     }
 }
 
+// Player 0 plays with WASD and space, player 1 with the arrows and enter
+const GAME_KEYS: [(KeyCode, u8, Input); 10] = [
+    (KeyCode::KeyW, 0, Input::Up),
+    (KeyCode::KeyS, 0, Input::Down),
+    (KeyCode::KeyA, 0, Input::Left),
+    (KeyCode::KeyD, 0, Input::Right),
+    (KeyCode::Space, 0, Input::Button),
+    (KeyCode::ArrowUp, 1, Input::Up),
+    (KeyCode::ArrowDown, 1, Input::Down),
+    (KeyCode::ArrowLeft, 1, Input::Left),
+    (KeyCode::ArrowRight, 1, Input::Right),
+    (KeyCode::Enter, 1, Input::Button),
+];
+
+fn game_input(keys: Res<ButtonInput<KeyCode>>, mut r: ResMut<LEDRenderEngine>) {
+    for (key, player, input) in GAME_KEYS {
+        if keys.just_pressed(key) {
+            r.engine.input(player, input);
+        }
+    }
+}
+
+// There is no audio in the simulator, so make up some music at 120 BPM to
+// drive the audio-reactive effects
+fn fake_audio(time: Res<Time>, mut r: ResMut<LEDRenderEngine>, mut beats: Local<u32>) {
+    let t = time.elapsed_secs();
+    let beat = (t * 2.0) as u32;
+    let mut features = AudioFeatures::default();
+    for band in 0..MAX_BANDS {
+        let wobble = 0.5 + 0.5 * (t * (1.0 + band as f32 * 0.37)).sin();
+        let falloff = 1.0 - band as f32 / MAX_BANDS as f32 * 0.6;
+        let _ = features.bands.push((wobble * falloff * 255.0) as u8);
+    }
+    features.level = (features.bands.iter().map(|b| *b as u32).sum::<u32>() / MAX_BANDS as u32) as u8;
+    features.beat = beat != *beats;
+    *beats = beat;
+    r.engine.set_audio(&features);
+}
+
 fn update_offscreen_render(
     time: Res<Time>,
     mut r: ResMut<LEDRenderEngine>,
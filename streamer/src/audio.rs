@@ -0,0 +1,208 @@
+// Audio analysis for the audio-reactive effects. The lights only get a compact
+// feature frame per block: the energy in each band, the overall level and a
+// beat flag. Input is raw signed 16 bit mono PCM, e.g.
+//
+//     arecord -f S16_LE -r 44100 -c 1 -t raw | streamer -i ... -p ... audio
+
+use std::collections::VecDeque;
+use std::io::Read;
+
+use command::AudioFrame;
+
+// Range the bands are spread over, logarithmically
+const MIN_FREQUENCY: f32 = 40.0;
+const MAX_FREQUENCY: f32 = 16000.0;
+// Quietest level that still lights anything, in dB below full scale
+const FLOOR_DB: f32 = -60.0;
+// A beat is bass this many times louder than the last second's average
+const BEAT_THRESHOLD: f32 = 1.5;
+const BEAT_MIN_ENERGY: f32 = 1e-4;
+// Fraction of a second after a beat before the next can be detected
+const BEAT_COOLDOWN: f32 = 0.2;
+
+pub struct Analyser {
+    block: usize,
+    fft_size: usize,
+    // FFT bins at the edges of each band
+    edges: Vec<usize>,
+    // Bass energy for the last second of blocks
+    history: VecDeque<f32>,
+    history_len: usize,
+    cooldown: u32,
+    cooldown_len: u32,
+}
+
+impl Analyser {
+    pub fn new(rate: u32, bands: usize, fps: u32) -> Self {
+        let block = (rate / fps.max(1)).max(1) as usize;
+        let fft_size = block.next_power_of_two().max(512);
+        let max = MAX_FREQUENCY.min(rate as f32 / 2.0);
+        let bin = |frequency: f32| (frequency * fft_size as f32 / rate as f32) as usize;
+        let edges = (0..=bands)
+            .map(|band| bin(MIN_FREQUENCY * (max / MIN_FREQUENCY).powf(band as f32 / bands as f32)))
+            .collect();
+        Self {
+            block,
+            fft_size,
+            edges,
+            history: VecDeque::new(),
+            history_len: fps.max(1) as usize,
+            cooldown: 0,
+            cooldown_len: (fps as f32 * BEAT_COOLDOWN) as u32,
+        }
+    }
+
+    // Samples per frame
+    pub fn block_size(&self) -> usize {
+        self.block
+    }
+
+    pub fn analyse(&mut self, samples: &[i16]) -> AudioFrame {
+        let n = samples.len().min(self.fft_size);
+        let mut re = vec![0.0; self.fft_size];
+        let mut im = vec![0.0; self.fft_size];
+        for (i, sample) in samples.iter().take(n).enumerate() {
+            // Hann window
+            let window = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / n as f32).cos();
+            re[i] = *sample as f32 / 32768.0 * window;
+        }
+        fft(&mut re, &mut im);
+
+        // A full scale sine through the window peaks at a quarter of the block
+        let full_scale = (n as f32 / 4.0).max(1.0);
+        let powers: Vec<f32> = self
+            .edges
+            .windows(2)
+            .map(|edge| {
+                let (low, high) = (edge[0], edge[1].max(edge[0] + 1));
+                let peak = (low..high.min(self.fft_size / 2))
+                    .map(|k| (re[k] * re[k] + im[k] * im[k]).sqrt())
+                    .fold(0.0, f32::max);
+                (peak / full_scale).powi(2)
+            })
+            .collect();
+
+        // Bands past MAX_BANDS would not fit in the frame, main checks for them
+        let bands = powers.iter().take(command::MAX_BANDS).map(|power| to_byte(10.0 * power.max(1e-12).log10())).collect();
+
+        let rms = (samples.iter().map(|s| (*s as f32 / 32768.0).powi(2)).sum::<f32>() / samples.len().max(1) as f32).sqrt();
+        let level = to_byte(20.0 * rms.max(1e-6).log10());
+
+        AudioFrame {
+            bands,
+            level,
+            beat: self.detect_beat(&powers),
+        }
+    }
+
+    // Onset detection on the bass, the lowest quarter of the bands
+    fn detect_beat(&mut self, powers: &[f32]) -> bool {
+        let bass = &powers[..powers.len().div_ceil(4)];
+        let energy = bass.iter().sum::<f32>() / bass.len().max(1) as f32;
+        let average = self.history.iter().sum::<f32>() / self.history.len().max(1) as f32;
+
+        self.history.push_back(energy);
+        if self.history.len() > self.history_len {
+            self.history.pop_front();
+        }
+
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return false;
+        }
+        let beat = energy > BEAT_MIN_ENERGY && energy > average * BEAT_THRESHOLD;
+        if beat {
+            self.cooldown = self.cooldown_len;
+        }
+        beat
+    }
+
+    // Read a block of samples, None at the end of the input
+    pub fn read_block(&self, input: &mut impl Read) -> std::io::Result<Option<Vec<i16>>> {
+        let mut bytes = vec![0; self.block_size() * 2];
+        match input.read_exact(&mut bytes) {
+            Ok(()) => Ok(Some(bytes.chunks_exact(2).map(|b| i16::from_le_bytes([b[0], b[1]])).collect())),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn to_byte(db: f32) -> u8 {
+    ((db - FLOOR_DB) / -FLOOR_DB * 255.0).clamp(0.0, 255.0) as u8
+}
+
+// In place radix 2 FFT, the length must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f32::consts::PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let (tr, ti) = (re[b] * cos - im[b] * sin, re[b] * sin + im[b] * cos);
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frequency: f32, amplitude: f32, rate: u32, len: usize) -> Vec<i16> {
+        (0..len)
+            .map(|i| ((2.0 * std::f32::consts::PI * frequency * i as f32 / rate as f32).sin() * amplitude * 32767.0) as i16)
+            .collect()
+    }
+
+    #[test]
+    fn test_tone_lights_its_band() {
+        let mut analyser = Analyser::new(44100, 16, 25);
+        let samples = tone(1000.0, 1.0, 44100, analyser.block_size());
+        let frame = analyser.analyse(&samples);
+
+        let loudest = frame.bands.iter().enumerate().max_by_key(|(_, b)| **b).unwrap().0;
+        let band = analyser.edges.windows(2).position(|e| (e[0]..e[1]).contains(&(1000 * analyser.fft_size / 44100))).unwrap();
+        assert_eq!(loudest, band);
+        assert!(frame.bands[loudest] > 240);
+        assert!(frame.level > 240);
+    }
+
+    #[test]
+    fn test_beat_after_silence() {
+        let mut analyser = Analyser::new(44100, 16, 25);
+        let silence = vec![0; analyser.block_size()];
+        for _ in 0..25 {
+            let frame = analyser.analyse(&silence);
+            assert!(!frame.beat);
+            assert_eq!(frame.level, 0);
+        }
+
+        let kick = tone(60.0, 0.8, 44100, analyser.block_size());
+        assert!(analyser.analyse(&kick).beat);
+        // One beat per kick, not one per block it lasts for
+        assert!(!analyser.analyse(&kick).beat);
+    }
+}
@@ -0,0 +1,292 @@
+// The command connection to one or more devices. Commands are either sent
+// and forgotten, for streams of frames and audio, or sent as a request that
+// waits for every device to reply. Failures a device reports for commands
+// sent without waiting are printed as they arrive.
+//
+// Every envelope carries the address the devices were targeted by, so a
+// device that is not part of it drops the command.
+//
+// Presentation times for streamed frames are in milliseconds since the
+// connection was opened. SyncClock tells the devices what that clock reads.
+
+use std::error::Error;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use command::{Address, Capabilities, Command, Decoder, Reply, Request, Response};
+
+use crate::devices::Device;
+
+// How long to wait for a device to reply
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+struct Peer {
+    name: String,
+    stream: TcpStream,
+    replies: Decoder<{ command::MAX_RESPONSE_LEN }, Reply>,
+}
+
+impl Peer {
+    fn connect(name: String, address: impl ToSocketAddrs) -> Result<Self, Box<dyn Error>> {
+        let stream = TcpStream::connect(address).map_err(|e| format!("could not connect to {}: {}", name, e))?;
+        Ok(Self { name, stream, replies: Decoder::new() })
+    }
+
+    // Read what has arrived, waiting up to timeout for something if given
+    fn read(&mut self, timeout: Option<Duration>) -> Result<(), Box<dyn Error>> {
+        match timeout {
+            Some(timeout) => {
+                self.stream.set_nonblocking(false)?;
+                self.stream.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+            }
+            None => self.stream.set_nonblocking(true)?,
+        }
+        let result = self.stream.read(self.replies.space());
+        self.stream.set_nonblocking(false)?;
+        match result {
+            Ok(0) => Err(format!("{} closed the connection", self.name).into()),
+            Ok(n) => {
+                self.replies.filled(n);
+                Ok(())
+            }
+            Err(e) if matches!(e.kind(), std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // The next reply read so far, printing anything that is not one
+    fn next_reply(&mut self) -> Option<Reply> {
+        while let Some(result) = self.replies.decode() {
+            match result {
+                Ok(reply) => return Some(reply),
+                Err(e) => eprintln!("Dropped part of a reply from {}: {:?}", self.name, e),
+            }
+        }
+        None
+    }
+
+    fn report(&self, reply: &Reply) {
+        if !reply.response.is_ok() {
+            match reply.id {
+                Some(id) => eprintln!("{} replied to request {}: {:?}", self.name, id, reply.response),
+                None => eprintln!("{} reported: {:?}", self.name, reply.response),
+            }
+        }
+    }
+
+    // Wait for the reply to request `id`, reporting anything else on the way
+    fn reply_to(&mut self, id: u32, deadline: Instant) -> Result<Response, Box<dyn Error>> {
+        loop {
+            while let Some(reply) = self.next_reply() {
+                if reply.id == Some(id) {
+                    return Ok(reply.response);
+                }
+                self.report(&reply);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(format!("timed out waiting for {} to reply to request {}", self.name, id).into());
+            }
+            self.read(Some(remaining))?;
+        }
+    }
+}
+
+pub struct Connection {
+    peers: Vec<Peer>,
+    address: Address,
+    next_id: u32,
+    opened: Instant,
+}
+
+impl Connection {
+    // A single device, by its network address
+    pub fn connect(address: impl ToSocketAddrs + std::fmt::Display) -> Result<Self, Box<dyn Error>> {
+        let peer = Peer::connect(address.to_string(), address)?;
+        Ok(Self::new(vec![peer], Address::All))
+    }
+
+    // The devices a target resolved to, with the address it resolved to
+    pub fn connect_to(devices: &[&Device], address: Address) -> Result<Self, Box<dyn Error>> {
+        let peers = devices
+            .iter()
+            .map(|device| Peer::connect(device.name.clone(), device.address.as_str()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(peers, address))
+    }
+
+    fn new(peers: Vec<Peer>, address: Address) -> Self {
+        Self {
+            peers,
+            address,
+            next_id: 1,
+            opened: Instant::now(),
+        }
+    }
+
+    fn write(&mut self, id: Option<u32>, command: Command) -> Result<(), Box<dyn Error>> {
+        let request = Request { id, command };
+        let mut buf = [0; command::MAX_ENVELOPE_LEN];
+        let bytes = command::encode_to(self.address, &request, &mut buf).map_err(|e| format!("could not encode {:?}: {:?}", request.command, e))?;
+        for peer in &mut self.peers {
+            peer.stream.write_all(bytes)?;
+            peer.stream.flush()?;
+        }
+        Ok(())
+    }
+
+    // Send without waiting for a reply
+    pub fn send(&mut self, command: Command) -> Result<(), Box<dyn Error>> {
+        self.write(None, command)?;
+        for peer in &mut self.peers {
+            peer.read(None)?;
+            while let Some(reply) = peer.next_reply() {
+                peer.report(&reply);
+            }
+        }
+        Ok(())
+    }
+
+    // Send and wait for every device to reply, with the name of each
+    pub fn request_each(&mut self, command: Command) -> Result<Vec<(String, Response)>, Box<dyn Error>> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        self.write(Some(id), command)?;
+
+        let deadline = Instant::now() + TIMEOUT;
+        self.peers
+            .iter_mut()
+            .map(|peer| Ok((peer.name.clone(), peer.reply_to(id, deadline)?)))
+            .collect()
+    }
+
+    // Send and fail if any device does not accept it
+    pub fn execute(&mut self, command: Command) -> Result<(), Box<dyn Error>> {
+        let failures: Vec<String> = self
+            .request_each(command)?
+            .into_iter()
+            .filter(|(_, response)| !response.is_ok())
+            .map(|(name, response)| format!("{} replied {:?}", name, response))
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join(", ").into())
+        }
+    }
+
+    // The connection clock at `at`, for presentation times
+    pub fn millis(&self, at: Instant) -> u32 {
+        at.saturating_duration_since(self.opened).as_millis() as u32
+    }
+
+    // Worth doing every frame or so, the devices keep the sample that was
+    // delayed least on the way
+    pub fn sync_clock(&mut self) -> Result<(), Box<dyn Error>> {
+        let now = self.millis(Instant::now());
+        self.send(Command::SyncClock(now))
+    }
+
+    pub fn query_each(&mut self) -> Result<Vec<(String, Capabilities)>, Box<dyn Error>> {
+        self.request_each(Command::Query)?
+            .into_iter()
+            .map(|(name, response)| match response {
+                Response::Capabilities(capabilities) => Ok((name, capabilities)),
+                response => Err(format!("expected capabilities, {} replied {:?}", name, response).into()),
+            })
+            .collect()
+    }
+
+    // The first device's, where they are all expected to be alike
+    pub fn query(&mut self) -> Result<Capabilities, Box<dyn Error>> {
+        let (_, capabilities) = self.query_each()?.into_iter().next().ok_or("there are no devices to query")?;
+        Ok(capabilities)
+    }
+}
+
+pub fn print_capabilities(capabilities: &Capabilities) {
+    println!("Firmware:  {}", capabilities.firmware);
+    println!("Protocol:  {}", capabilities.protocol_version);
+    println!("Size:      {} x {}", capabilities.width, capabilities.height);
+    println!("Layout:    {:?}", capabilities.layout);
+    println!("Frames:    {} per second", capabilities.fps);
+    println!("Device:    {}", capabilities.device);
+    println!("Groups:    {:?}", capabilities.groups);
+    println!("Animations:");
+    for animation in &capabilities.animations {
+        println!("  {:?}", animation);
+    }
+    println!("Params:");
+    for param in &capabilities.params {
+        println!("  {:?}", param);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use command::Identity;
+    use std::net::TcpListener;
+
+    fn identity(device: u16, groups: &[u16]) -> Identity {
+        Identity { device, groups: groups.iter().copied().collect() }
+    }
+
+    // A device that answers each request with a failure report, then the reply
+    fn fake_device(listener: TcpListener, replies: usize, identity: Identity) {
+        let (mut socket, _) = listener.accept().unwrap();
+        let mut requests = Decoder::<{ command::MAX_ENVELOPE_LEN }, Request>::new().with_identity(identity);
+        let mut answered = 0;
+        while answered < replies {
+            let n = socket.read(requests.space()).unwrap();
+            requests.filled(n);
+            while let Some(Ok(request)) = requests.decode() {
+                let mut buf = [0; command::MAX_RESPONSE_LEN];
+                let failure = Reply { id: None, response: Response::Error(command::ErrorCode::BadFrame) };
+                socket.write_all(command::encode(&failure, &mut buf).unwrap()).unwrap();
+                let reply = Reply { id: request.id, response: Response::Ok };
+                socket.write_all(command::encode(&reply, &mut buf).unwrap()).unwrap();
+                answered += 1;
+            }
+        }
+        // Hold the connection open without answering
+        std::thread::sleep(TIMEOUT + Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_requests() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let device = std::thread::spawn(move || fake_device(listener, 2, identity(1, &[])));
+
+        let mut connection = Connection::connect(address).unwrap();
+        assert_eq!(connection.request_each(Command::Flush).unwrap(), [(address.to_string(), Response::Ok)]);
+        connection.execute(Command::TapTempo).unwrap();
+        // No reply comes for the third
+        assert!(connection.request_each(Command::Flush).is_err());
+        device.join().unwrap();
+    }
+
+    #[test]
+    fn test_groups() {
+        // Each device only answers what is addressed to it
+        let devices: Vec<Device> = [1, 2]
+            .into_iter()
+            .map(|id| {
+                let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                let address = listener.local_addr().unwrap().to_string();
+                let groups = vec![5, 5 + id];
+                let device_identity = identity(id, &groups);
+                std::thread::spawn(move || fake_device(listener, 1, device_identity));
+                Device { name: format!("window {}", id), id, address, groups }
+            })
+            .collect();
+        let targets: Vec<&Device> = devices.iter().collect();
+
+        let mut connection = Connection::connect_to(&targets, Address::Group(5)).unwrap();
+        let replies = connection.request_each(Command::Flush).unwrap();
+        assert_eq!(replies, [("window 1".to_string(), Response::Ok), ("window 2".to_string(), Response::Ok)]);
+    }
+}
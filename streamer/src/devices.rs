@@ -0,0 +1,148 @@
+// The devices file names the controllers and groups commands can be sent to,
+// one per line:
+//
+//     # group <name> <id>
+//     group roofline 1
+//     group windows 2
+//     # device <name> <id> <host:port> [group...]
+//     device roof 10 192.168.1.220:1234 roofline
+//     device kitchen 1 192.168.1.214:1234 windows
+//
+// Ids are what each device is built with, from the DEVICE_ID and
+// DEVICE_GROUPS environment variables when the firmware is built:
+//
+//     DEVICE_ID=10 DEVICE_GROUPS=1 cargo build --release
+//
+// and device 1 in group 1 without them. A target is a device name, a group
+// name, or "all".
+
+use std::error::Error;
+use std::path::Path;
+
+use command::Address;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    pub name: String,
+    pub id: u16,
+    pub address: String,
+    pub groups: Vec<u16>,
+}
+
+#[derive(Debug, Default)]
+pub struct Devices {
+    groups: Vec<(String, u16)>,
+    devices: Vec<Device>,
+}
+
+impl Devices {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut devices = Devices::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let id = |word: &str| word.parse::<u16>().map_err(|e| format!("line {}: bad id {:?}: {}", number + 1, word, e));
+            match words[..] {
+                [] => {}
+                ["group", name, group] => {
+                    devices.check_name(name, number)?;
+                    devices.groups.push((name.to_string(), id(group)?));
+                }
+                ["device", name, device, address, ref groups @ ..] => {
+                    devices.check_name(name, number)?;
+                    let groups = groups
+                        .iter()
+                        .map(|group| devices.group(group).ok_or_else(|| format!("line {}: no group {:?}", number + 1, group)))
+                        .collect::<Result<_, _>>()?;
+                    devices.devices.push(Device { name: name.to_string(), id: id(device)?, address: address.to_string(), groups });
+                }
+                _ => return Err(format!("line {}: expected a group or device, got {:?}", number + 1, line.trim())),
+            }
+        }
+        Ok(devices)
+    }
+
+    fn check_name(&self, name: &str, number: usize) -> Result<(), String> {
+        if name == "all" || self.group(name).is_some() || self.devices.iter().any(|device| device.name == name) {
+            return Err(format!("line {}: {:?} is already taken", number + 1, name));
+        }
+        Ok(())
+    }
+
+    fn group(&self, name: &str) -> Option<u16> {
+        self.groups.iter().find(|(group, _)| group == name).map(|(_, id)| *id)
+    }
+
+    // The address to put on commands and the devices to send them to
+    pub fn resolve(&self, target: &str) -> Result<(Address, Vec<&Device>), String> {
+        let (address, devices): (Address, Vec<&Device>) = if target == "all" {
+            (Address::All, self.devices.iter().collect())
+        } else if let Some(device) = self.devices.iter().find(|device| device.name == target) {
+            (Address::Device(device.id), vec![device])
+        } else if let Some(group) = self.group(target) {
+            (Address::Group(group), self.devices.iter().filter(|device| device.groups.contains(&group)).collect())
+        } else {
+            return Err(format!("no device or group called {:?}", target));
+        };
+        if devices.is_empty() {
+            return Err(format!("there are no devices in {:?}", target));
+        }
+        Ok((address, devices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICES: &str = "
+        group roofline 1
+        group windows 2  # every window
+        device roof 10 192.168.1.220:1234 roofline
+        device kitchen 1 192.168.1.214:1234 windows
+        device lounge 2 192.168.1.215:1234 windows roofline
+    ";
+
+    fn names(devices: &[&Device]) -> Vec<String> {
+        devices.iter().map(|device| device.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_resolve() {
+        let devices = Devices::parse(DEVICES).unwrap();
+
+        let (address, targets) = devices.resolve("kitchen").unwrap();
+        assert_eq!(address, Address::Device(1));
+        assert_eq!(targets[0].address, "192.168.1.214:1234");
+
+        let (address, targets) = devices.resolve("roofline").unwrap();
+        assert_eq!(address, Address::Group(1));
+        assert_eq!(names(&targets), ["roof", "lounge"]);
+
+        let (address, targets) = devices.resolve("all").unwrap();
+        assert_eq!(address, Address::All);
+        assert_eq!(targets.len(), 3);
+
+        assert!(devices.resolve("garage").is_err());
+    }
+
+    #[test]
+    fn test_bad_files() {
+        for bad in [
+            "device roof 10 192.168.1.220:1234 roofline",
+            "group roofline 1\ngroup roofline 2",
+            "group windows -1",
+            "device all 1 192.168.1.214:1234",
+            "light roof",
+        ] {
+            assert!(Devices::parse(bad).is_err(), "{}", bad);
+        }
+        let empty = Devices::parse("group empty 3").unwrap();
+        assert!(empty.resolve("empty").is_err());
+    }
+}
@@ -1,8 +1,12 @@
-//mod stream_frame;
+mod audio;
+mod connection;
+mod devices;
+mod stream_frame;
 
-use std::io::prelude::*;
 use std::fs::File;
-use std::net::{Ipv4Addr, TcpStream};
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
 
 
@@ -12,6 +16,9 @@ use image::{ImageBuffer, ImageReader};
 use image::codecs::gif::GifDecoder;
 use image::AnimationDecoder;
 use command::Command as StreamCommand;
+use connection::Connection;
+use devices::Devices;
+use stream_frame::{FrameEncoding, FrameStream};
 
 
 
@@ -19,10 +26,17 @@ use command::Command as StreamCommand;
 
 #[derive(Parser)]
 struct Cli {
+    /// A device to connect to directly, rather than a --target
     #[clap(short, long)]
-    ip: Ipv4Addr,
-    #[clap(short, long)]
+    ip: Option<Ipv4Addr>,
+    #[clap(short, long, default_value_t = 1234)]
     port: u16,
+    /// A device or group from the devices file, or all for every device in it
+    #[clap(short, long, conflicts_with = "ip")]
+    target: Option<String>,
+    /// Lists the devices and groups a --target can name
+    #[clap(short, long, default_value = "devices.txt")]
+    devices: PathBuf,
     #[clap(subcommand)]
     command: Command,
 }
@@ -34,6 +48,26 @@ enum Command {
     Animate(AnimateArgs),
     Flush,
     Display(DisplayArgs),
+    Text(TextArgs),
+    Time(TimeArgs),
+    Countdown(CountdownArgs),
+    Input(InputArgs),
+    Play,
+    Audio(AudioArgs),
+    Bpm(BpmArgs),
+    Tap,
+    Sync,
+    /// Show what the device is and can do
+    Query,
+    /// Change a setting of the running animation
+    Param(ParamArgs),
+    /// Dim the display without changing what is drawn
+    Brightness(BrightnessArgs),
+    /// Draw into the buffer, shown on the next flush
+    ///
+    /// Colours are hex, e.g. ff8000 or #ff8000. Coordinates can be negative or
+    /// off the display, the device clips what does not fit.
+    Draw(DrawArgs),
 }
 
 #[derive(clap::Args)]
@@ -45,38 +79,334 @@ struct ClearArgs {
 
 #[derive(clap::Args)]
 struct AnimateArgs {
+    /// Seconds to change over from the current animation, at once when left out
+    #[clap(short, long)]
+    transition: Option<f32>,
+    /// How the current animation gives way to the new one
+    #[clap(short, long, value_enum, default_value_t = TransitionStyle::Crossfade)]
+    style: TransitionStyle,
+    /// Set before the animation starts, e.g. --param speed=2
+    #[clap(short, long, value_parser = parse_param)]
+    param: Vec<command::Param>,
     #[clap(subcommand)]
     subcommand: Animation,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum TransitionStyle {
+    Cut,
+    Crossfade,
+    FadeThroughBlack,
+    Wipe,
+}
+
+impl From<TransitionStyle> for command::TransitionStyle {
+    fn from(style: TransitionStyle) -> Self {
+        match style {
+            TransitionStyle::Cut => command::TransitionStyle::Cut,
+            TransitionStyle::Crossfade => command::TransitionStyle::Crossfade,
+            TransitionStyle::FadeThroughBlack => command::TransitionStyle::FadeThroughBlack,
+            TransitionStyle::Wipe => command::TransitionStyle::Wipe,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct ParamArgs {
+    /// speed=<factor>, fire-cooling=<0-255> or fire-sparking=<0-255>
+    #[clap(value_parser = parse_param)]
+    param: command::Param,
+}
+
+#[derive(clap::Args)]
+struct BrightnessArgs {
+    /// 0 is off, 255 full brightness
+    brightness: u8,
+}
+
+#[derive(clap::Args)]
+struct DrawArgs {
+    #[clap(subcommand)]
+    shape: Shape,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Shape {
+    /// Fill a rectangle from its top left corner
+    #[clap(allow_negative_numbers = true)]
+    Rect {
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        #[clap(value_parser = parse_color)]
+        color: command::Rgb,
+    },
+    /// A line from one point to another, both ends included
+    #[clap(allow_negative_numbers = true)]
+    Line {
+        x0: i16,
+        y0: i16,
+        x1: i16,
+        y1: i16,
+        #[clap(value_parser = parse_color)]
+        color: command::Rgb,
+    },
+    /// Fill a rectangle left to right, or top to bottom with --vertical
+    #[clap(allow_negative_numbers = true)]
+    Gradient {
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        #[clap(value_parser = parse_color)]
+        from: command::Rgb,
+        #[clap(value_parser = parse_color)]
+        to: command::Rgb,
+        #[clap(short, long)]
+        vertical: bool,
+    },
+    /// A circle around a centre, outlined unless --filled
+    #[clap(allow_negative_numbers = true)]
+    Circle {
+        x: i16,
+        y: i16,
+        radius: u16,
+        #[clap(value_parser = parse_color)]
+        color: command::Rgb,
+        #[clap(short, long)]
+        filled: bool,
+    },
+    /// Text in the 5 pixel high font from its top left, --scale times as big
+    #[clap(allow_negative_numbers = true)]
+    Text {
+        x: i16,
+        y: i16,
+        #[clap(value_parser = parse_color)]
+        color: command::Rgb,
+        text: String,
+        #[clap(short, long, default_value_t = 1)]
+        scale: u8,
+    },
+}
+
+impl TryFrom<Shape> for StreamCommand {
+    type Error = String;
+
+    fn try_from(shape: Shape) -> Result<Self, Self::Error> {
+        Ok(match shape {
+            Shape::Rect { x, y, width, height, color } => StreamCommand::FillRect(command::Rect { x, y, width, height }, color),
+            Shape::Line { x0, y0, x1, y1, color } => {
+                StreamCommand::Line(command::Point { x: x0, y: y0 }, command::Point { x: x1, y: y1 }, color)
+            }
+            Shape::Gradient { x, y, width, height, from, to, vertical } => {
+                let rect = command::Rect { x, y, width, height };
+                if vertical {
+                    StreamCommand::VerticalGradient(rect, from, to)
+                } else {
+                    StreamCommand::HorizontalGradient(rect, from, to)
+                }
+            }
+            Shape::Circle { x, y, radius, color, filled } => StreamCommand::Circle(command::Point { x, y }, radius, color, filled),
+            Shape::Text { x, y, color, text, scale } => {
+                let text = command::Text::try_from(text.as_str())
+                    .map_err(|_| format!("text is longer than {} bytes", command::MAX_TEXT_LEN))?;
+                StreamCommand::Text(command::Point { x, y }, color, scale, text)
+            }
+        })
+    }
+}
+
 #[derive(clap::Subcommand, Debug)]
 enum Animation {
     None,
     Snow,
     Sparkle,
-    Rainbow
+    Rainbow,
+    Marquee,
+    Fire,
+    Fireworks,
+    Life,
+    Elementary {
+        #[clap(default_value_t = 30)]
+        rule: u8,
+    },
+    Plasma,
+    Aurora,
+    Icicle,
+    Matrix,
+    Countdown,
+    Snake,
+    Pong,
+    VuMeter,
+    BeatFlash,
+    Waterfall,
+    Pulse,
 }
 
 #[derive(clap::Args)]
 #[derive(Clone)]
 struct DisplayArgs {
+    /// Width, asked of the device when left out
     #[clap(short, long)]
-    x: u32,
+    x: Option<u32>,
+    /// Height, asked of the device when left out
     #[clap(short, long)]
-    y: u32,
+    y: Option<u32>,
     #[clap(short, long)]
     source: String,
+    /// Animations play at 2 frames per second when left out
     #[clap(short, long)]
     fps: Option<u32>,
+    #[clap(short, long, value_enum, default_value_t = FrameEncoding::Auto)]
+    encoding: FrameEncoding,
+    /// Frames between full keyframes when playing an animation
+    #[clap(short, long, default_value_t = 30)]
+    keyframe_interval: u32,
+    /// Milliseconds animation frames are sent ahead of being shown, to smooth
+    /// over Wi-Fi jitter. 0 shows each frame as soon as it arrives.
+    #[clap(short, long, default_value_t = 150)]
+    latency: u32,
+}
+
+#[derive(clap::Args)]
+struct TextArgs {
+    text: String,
+}
+
+#[derive(clap::Args)]
+struct TimeArgs {
+    /// Minutes to add to UTC to get local time
+    #[clap(short, long, default_value_t = 0, allow_hyphen_values = true)]
+    utc_offset: i64,
+}
+
+#[derive(clap::Args)]
+struct CountdownArgs {
+    /// YYYY-MM-DD, counts down to the next Christmas when left out
+    date: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct InputArgs {
+    #[clap(short, long, default_value_t = 0)]
+    player: u8,
+    #[clap(value_enum)]
+    event: Input,
+}
+
+#[derive(clap::Args)]
+struct AudioArgs {
+    /// Sample rate of the PCM on stdin
+    #[clap(short, long, default_value_t = 44100)]
+    rate: u32,
+    #[clap(short, long, default_value_t = command::MAX_BANDS)]
+    bands: usize,
+    /// Feature frames sent per second
+    #[clap(short, long, default_value_t = 25)]
+    fps: u32,
+}
+
+#[derive(clap::Args)]
+struct BpmArgs {
+    /// Beats per minute, 0 stops the beat clock
+    bpm: f32,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Input {
+    Up,
+    Down,
+    Left,
+    Right,
+    Button,
+}
+
+impl From<Input> for command::Input {
+    fn from(input: Input) -> Self {
+        match input {
+            Input::Up => command::Input::Up,
+            Input::Down => command::Input::Down,
+            Input::Left => command::Input::Left,
+            Input::Right => command::Input::Right,
+            Input::Button => command::Input::Button,
+        }
+    }
+}
+
+// Controller keys for play mode: player 0 on the left of the keyboard, player 1 on the right
+fn input_for_key(key: char) -> Option<(u8, command::Input)> {
+    match key.to_ascii_lowercase() {
+        'w' => Some((0, command::Input::Up)),
+        'a' => Some((0, command::Input::Left)),
+        's' => Some((0, command::Input::Down)),
+        'd' => Some((0, command::Input::Right)),
+        'e' => Some((0, command::Input::Button)),
+        'i' => Some((1, command::Input::Up)),
+        'j' => Some((1, command::Input::Left)),
+        'k' => Some((1, command::Input::Down)),
+        'l' => Some((1, command::Input::Right)),
+        'o' => Some((1, command::Input::Button)),
+        _ => None,
+    }
+}
+
+fn parse_color(color: &str) -> Result<command::Rgb, String> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    let value = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)
+        .ok_or_else(|| format!("expected a colour like ff8000, got {:?}", color))?;
+    Ok(((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+fn parse_param(param: &str) -> Result<command::Param, String> {
+    let (name, value) = param.split_once('=').ok_or_else(|| format!("expected name=value, got {:?}", param))?;
+    let error = |e: &dyn std::fmt::Display| format!("bad value for {}: {}", name, e);
+    match name {
+        "speed" => value.parse().map(command::Param::Speed).map_err(|e| error(&e)),
+        "fire-cooling" => value.parse().map(command::Param::FireCooling).map_err(|e| error(&e)),
+        "fire-sparking" => value.parse().map(command::Param::FireSparking).map_err(|e| error(&e)),
+        _ => Err(format!("unknown parameter {:?}, expected speed, fire-cooling or fire-sparking", name)),
+    }
 }
 
-impl Into<command::Animation> for Animation {
-    fn into(self) -> command::Animation {
-        match self {
+fn parse_date(date: &str) -> Result<(u16, u8, u8), String> {
+    let error = || format!("expected a date like 2024-12-25, got {:?}", date);
+    let mut parts = date.split('-');
+    let (Some(year), Some(month), Some(day), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return Err(error());
+    };
+    let year = year.parse().map_err(|_| error())?;
+    let month = month.parse().map_err(|_| error())?;
+    let day = day.parse().map_err(|_| error())?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(error());
+    }
+    Ok((year, month, day))
+}
+
+impl From<Animation> for command::Animation {
+    fn from(animation: Animation) -> Self {
+        match animation {
             Animation::None => command::Animation::None,
             Animation::Snow => command::Animation::Snow,
             Animation::Sparkle => command::Animation::Sparkle,
             Animation::Rainbow => command::Animation::Rainbow,
+            Animation::Marquee => command::Animation::Marquee,
+            Animation::Fire => command::Animation::Fire,
+            Animation::Fireworks => command::Animation::Fireworks,
+            Animation::Life => command::Animation::Life,
+            Animation::Elementary { rule } => command::Animation::Elementary(rule),
+            Animation::Plasma => command::Animation::Plasma,
+            Animation::Aurora => command::Animation::Aurora,
+            Animation::Icicle => command::Animation::Icicle,
+            Animation::Matrix => command::Animation::Matrix,
+            Animation::Countdown => command::Animation::Countdown,
+            Animation::Snake => command::Animation::Snake,
+            Animation::Pong => command::Animation::Pong,
+            Animation::VuMeter => command::Animation::VuMeter,
+            Animation::BeatFlash => command::Animation::BeatFlash,
+            Animation::Waterfall => command::Animation::Waterfall,
+            Animation::Pulse => command::Animation::Pulse,
         }
     }
 }
@@ -86,7 +416,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let cli = Cli::parse();
 
-    let mut stream = TcpStream::connect("192.168.1.214:1234")?;
+    let mut stream = match (&cli.target, cli.ip) {
+        (Some(target), _) => {
+            let devices = Devices::load(&cli.devices)?;
+            let (address, targets) = devices.resolve(target)?;
+            Connection::connect_to(&targets, address)?
+        }
+        (None, Some(ip)) => Connection::connect(SocketAddrV4::new(ip, cli.port))?,
+        (None, None) => return Err("give a device with --ip, or a device or group with --target".into()),
+    };
 
     let command = match cli.command {
         Command::Clear(args) => {
@@ -96,7 +434,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         
         Command::Animate(args) => {
             println!("Animating with {:?}", args.subcommand);
-            StreamCommand::Animate(args.subcommand.into())
+            if args.transition.is_none() && args.param.is_empty() {
+                StreamCommand::Animate(args.subcommand.into())
+            } else {
+                if args.param.len() > command::MAX_PARAMS {
+                    return Err(format!("at most {} parameters can be set with an animation", command::MAX_PARAMS).into());
+                }
+                StreamCommand::AnimateWith {
+                    animation: args.subcommand.into(),
+                    transition_duration: args.transition.unwrap_or(0.0),
+                    style: args.style.into(),
+                    params: args.param.into_iter().collect(),
+                }
+            }
         }
         Command::Flush => {
             println!("Flushing the display");
@@ -106,20 +456,91 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             display(&mut stream, args)?;
             StreamCommand::Flush
         }
+        Command::Text(args) => {
+            println!("Setting the marquee text to {:?}", args.text);
+            let text = command::Text::try_from(args.text.as_str())
+                .map_err(|_| format!("text is longer than {} bytes", command::MAX_TEXT_LEN))?;
+            StreamCommand::SetText(text)
+        }
+        Command::Time(args) => {
+            let utc = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+            let local = utc.checked_add_signed(args.utc_offset * 60).ok_or("time is out of range")?;
+            println!("Setting the time to {} (UTC{:+} minutes)", local, args.utc_offset);
+            StreamCommand::SetTime(local)
+        }
+        Command::Countdown(args) => match args.date {
+            Some(date) => {
+                let (year, month, day) = parse_date(&date)?;
+                println!("Counting down to {:04}-{:02}-{:02}", year, month, day);
+                StreamCommand::SetCountdownTarget(year, month, day)
+            }
+            None => {
+                println!("Counting down to Christmas");
+                StreamCommand::ClearCountdownTarget
+            }
+        },
+        Command::Input(args) => {
+            println!("Player {} pressed {:?}", args.player, args.event);
+            StreamCommand::Input(args.player, args.event.into())
+        }
+        Command::Play => {
+            play(&mut stream)?;
+            StreamCommand::Flush
+        }
+        Command::Audio(args) => {
+            stream_audio(&mut stream, args)?;
+            StreamCommand::Flush
+        }
+        Command::Bpm(args) => {
+            println!("Setting the tempo to {} BPM", args.bpm);
+            StreamCommand::SetBpm(args.bpm)
+        }
+        Command::Tap => {
+            tap(&mut stream)?;
+            StreamCommand::Flush
+        }
+        Command::Sync => {
+            println!("Syncing to the beat");
+            StreamCommand::SyncBeat
+        }
+        Command::Param(args) => {
+            println!("Setting {:?}", args.param);
+            StreamCommand::SetParam(args.param)
+        }
+        Command::Brightness(args) => {
+            println!("Setting the brightness to {}", args.brightness);
+            StreamCommand::SetBrightness(args.brightness)
+        }
+        Command::Draw(args) => {
+            println!("Drawing {:?}", args.shape);
+            args.shape.try_into()?
+        }
+        Command::Query => {
+            for (name, capabilities) in stream.query_each()? {
+                println!("{}", name);
+                connection::print_capabilities(&capabilities);
+            }
+            return Ok(());
+        }
     };
 
-    let buf = minicbor_serde::to_vec(&command)?;
-    stream.write(&buf)?;
-    stream.flush()?;
-
-    Ok(())
+    stream.execute(command)
 }
 
 
-fn display(stream: &mut TcpStream, args: DisplayArgs) -> Result<(), Box<dyn std::error::Error>> {
+fn display(stream: &mut Connection, args: DisplayArgs) -> Result<(), Box<dyn std::error::Error>> {
     let reader = ImageReader::open(args.source.clone())?;
     let format = reader.format().ok_or("unknown image format")?;
 
+    let (x, y) = match (args.x, args.y) {
+        (Some(x), Some(y)) => (x, y),
+        _ => {
+            let capabilities = stream.query()?;
+            println!("Display is {} x {}", capabilities.width, capabilities.height);
+            (args.x.unwrap_or(capabilities.width as u32), args.y.unwrap_or(capabilities.height as u32))
+        }
+    };
+
 
     if format == image::ImageFormat::Gif {
         println!("GIF");
@@ -128,54 +549,92 @@ fn display(stream: &mut TcpStream, args: DisplayArgs) -> Result<(), Box<dyn std:
         let file = File::open(path)?;
         let decoder = GifDecoder::new(std::io::BufReader::new(file))?;
         let frames = decoder.into_frames().collect_frames()?;
-        let x = args.x;
-        let y = args.y;
+        let mut frame_stream = FrameStream::new(x as u16, args.encoding, args.keyframe_interval);
+        let interval = Duration::from_millis(args.fps.map_or(500, |fps| 1000 / fps.max(1) as u64));
+        let latency = Duration::from_millis(args.latency as u64);
+        let mut due = Instant::now();
         loop {
             for (index, frame) in frames.iter().enumerate() {
                 println!("Frame {}", index);
                 let buffer = frame.buffer();
                 let resized = resize(buffer, x, y, image::imageops::FilterType::CatmullRom);
-                send_frame(stream, args.clone(), resized)?;
-                std::thread::sleep(std::time::Duration::from_millis(500));
+                let present_at = (args.latency > 0).then(|| stream.millis(due + latency));
+                if present_at.is_some() {
+                    stream.sync_clock()?;
+                }
+                for command in frame_stream.next_frame(&pixels_of(&resized), present_at)? {
+                    send_command(stream, command)?;
+                }
+                if present_at.is_none() {
+                    send_command(stream, StreamCommand::Flush)?;
+                }
+                // Keep to the schedule however long sending took
+                due += interval;
+                std::thread::sleep(due.saturating_duration_since(Instant::now()));
             }   
         }
     } else {
         println!("Not GIF");
         let image = reader.decode()?;
         let buffer = image.to_rgba8();
-        let resized = resize(&buffer, args.x, args.y, image::imageops::FilterType::CatmullRom);
-        send_frame(stream, args,  resized)?;
+        let resized = resize(&buffer, x, y, image::imageops::FilterType::CatmullRom);
+        send_frame(stream, x as u16, args.encoding, resized)?;
     }
 
     Ok(())
 
 }
 
-fn send_frame(stream: &mut TcpStream, args: DisplayArgs, buffer: ImageBuffer<image::Rgba<u8>, Vec<u8>>) -> Result<(), Box<dyn std::error::Error>> {
-    send_command(stream, StreamCommand::Clear(0, 0, 0))?;
-    // Send the frame to the server
-    for (i, p) in buffer.pixels().enumerate() {
-        let x = i as u32 % args.x;
-        let y = i as u32 / args.x;
-
-        let set_pixel = StreamCommand::SetPixel(x as u8, y as u8, p[0], p[1], p[2]);
+// Turn the terminal into a controller. Each line typed is sent as a run of key
+// presses, so holding a key and pressing enter moves a paddle a long way.
+fn play(stream: &mut Connection) -> Result<(), Box<dyn std::error::Error>> {
+    println!("Player 0: w a s d, e for the button. Player 1: i j k l, o for the button.");
+    println!("Press enter to send, ctrl-d to stop.");
+    for line in std::io::stdin().lines() {
+        for (player, input) in line?.chars().filter_map(input_for_key) {
+            send_command(stream, StreamCommand::Input(player, input))?;
+        }
+    }
+    Ok(())
+}
 
-        send_command(stream, set_pixel)?;
+// Tap tempo from the terminal, every press of enter is a tap
+fn tap(stream: &mut Connection) -> Result<(), Box<dyn std::error::Error>> {
+    println!("Press enter on every beat, ctrl-d to stop.");
+    for line in std::io::stdin().lines() {
+        line?;
+        send_command(stream, StreamCommand::TapTempo)?;
     }
-    send_command(stream, StreamCommand::Flush)?;
+    Ok(())
+}
 
+// Analyse the PCM on stdin and send a feature frame for every block of it
+fn stream_audio(stream: &mut Connection, args: AudioArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if args.bands == 0 || args.bands > command::MAX_BANDS {
+        return Err(format!("bands must be between 1 and {}", command::MAX_BANDS).into());
+    }
+    let mut analyser = audio::Analyser::new(args.rate, args.bands, args.fps);
+    let mut input = std::io::stdin().lock();
+    println!("Streaming audio features, {} bands at {} fps", args.bands, args.fps);
+    while let Some(samples) = analyser.read_block(&mut input)? {
+        send_command(stream, StreamCommand::Audio(analyser.analyse(&samples)))?;
+    }
     Ok(())
 }
 
-fn send_command(stream: &mut TcpStream, command: StreamCommand) -> Result<(), Box<dyn std::error::Error>> {
-    let buf = minicbor_serde::to_vec(&command)?;
-    stream.write(&buf)?;
+fn pixels_of(buffer: &ImageBuffer<image::Rgba<u8>, Vec<u8>>) -> Vec<stream_frame::Pixel> {
+    buffer.pixels().map(|p| (p[0], p[1], p[2])).collect()
+}
 
-    if command == StreamCommand::Flush {
-        println!("Flushing {:?}", buf);
+fn send_frame(stream: &mut Connection, width: u16, encoding: FrameEncoding, buffer: ImageBuffer<image::Rgba<u8>, Vec<u8>>) -> Result<(), Box<dyn std::error::Error>> {
+    for frame in stream_frame::strips(&pixels_of(&buffer), width, encoding)? {
+        send_command(stream, StreamCommand::SetFrame(frame))?;
     }
-    // sleep for a bit
-//    std::thread::sleep(std::time::Duration::from_millis(15));
-    stream.flush()?;
+    send_command(stream, StreamCommand::Flush)?;
+
     Ok(())
-}
\ No newline at end of file
+}
+
+fn send_command(stream: &mut Connection, command: StreamCommand) -> Result<(), Box<dyn std::error::Error>> {
+    stream.send(command)
+}
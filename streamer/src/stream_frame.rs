@@ -1,54 +1,177 @@
-use serde::{Deserialize, Deserializer, Serialize};
-use serde::de::{self, Visitor, SeqAccess};
-use core::fmt;
-use core::marker::PhantomData;
-
-#[derive(Serialize, Deserialize)]
-struct StreamFrame {
-    width: usize, // The width and height of the image
-    height: usize,
-    x: i32,  // Where to render the image in the stage
-    y: i32,
-    #[serde(deserialize_with = "read_pixels")]
-//    #[serde(serialize_with = "write_pixels")]
-    pixel: (u8, u8, u8, u8),
+// Turning images into frame commands. Still images go as `SetFrame` strips,
+// animations as a stream of keyframes and deltas so only the pixels that
+// change between frames go over the wire.
+
+use std::error::Error;
+
+use command::{Command, Delta, Encoding, Frame, FrameHeader};
+
+pub type Pixel = (u8, u8, u8);
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum FrameEncoding {
+    // Whichever is smallest for each strip
+    Auto,
+    Raw,
+    Rle,
+    Palette,
 }
 
-fn read_pixels<'de, T, D>(deserialiser: D) -> Result<T, D::Error>
-where
-    D: Deserializer<'de>,
-    T: Deserialize<'de> + Ord,
-{
-    struct PixelVisitor<T>(PhantomData<fn() -> T>);
-
-    impl<'de, T> Visitor<'de> for PixelVisitor<T>
-    where
-        T: Deserialize<'de> + Ord,
-    {
-        type Value = T;
-
-        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("an RGBA<u8> pixel")
+// Encode a strip of rows, in the smallest encoding when left to choose
+fn encode_strip(y: u16, width: u16, pixels: &[Pixel], encoding: FrameEncoding) -> Result<Frame, Box<dyn Error>> {
+    let height = (pixels.len() / width as usize) as u16;
+    let encodings: &[Encoding] = match encoding {
+        FrameEncoding::Auto => &[Encoding::Raw, Encoding::Rle, Encoding::Palette],
+        FrameEncoding::Raw => &[Encoding::Raw],
+        FrameEncoding::Rle => &[Encoding::Rle],
+        FrameEncoding::Palette => &[Encoding::Palette],
+    };
+
+    let mut last_error = None;
+    let mut best: Option<Frame> = None;
+    for &encoding in encodings {
+        match Frame::encode(0, y, width, height, pixels, encoding) {
+            Ok(frame) if best.as_ref().is_none_or(|best| frame.data_len() < best.data_len()) => best = Some(frame),
+            Ok(_) => {}
+            Err(e) => last_error = Some(e),
         }
+    }
+    best.ok_or_else(|| format!("could not encode rows from {}: {:?}", y, last_error).into())
+}
 
-        fn visit_seq<S>(self, mut seq: S) -> Result<T, S::Error>
-        where
-            S: SeqAccess<'de>,
-        {
+// A whole frame as strips of rows, each small enough for one command
+pub fn strips(pixels: &[Pixel], width: u16, encoding: FrameEncoding) -> Result<Vec<Frame>, Box<dyn Error>> {
+    let width = width.max(1) as usize;
+    // As many rows as fit in a frame uncompressed, so every encoding fits
+    let rows = (command::MAX_FRAME_BYTES / (3 * width)).max(1);
+    pixels
+        .chunks(rows * width)
+        .enumerate()
+        .map(|(strip, chunk)| encode_strip((strip * rows) as u16, width as u16, chunk, encoding))
+        .collect()
+}
 
-            while let Some(pixel) = seq.next_element()? {
+pub struct FrameStream {
+    width: u16,
+    encoding: FrameEncoding,
+    // Frames between keyframes, so a device that missed a delta catches up
+    keyframe_interval: u32,
+    previous: Option<Vec<Pixel>>,
+    sequence: u32,
+    since_keyframe: u32,
+}
+
+impl FrameStream {
+    pub fn new(width: u16, encoding: FrameEncoding, keyframe_interval: u32) -> Self {
+        Self {
+            width,
+            encoding,
+            keyframe_interval,
+            previous: None,
+            sequence: 0,
+            since_keyframe: 0,
+        }
+    }
+
+    fn deltas(&self, previous: &[Pixel], pixels: &[Pixel]) -> Result<Vec<Delta>, Box<dyn Error>> {
+        let mut deltas = Vec::new();
+        let mut start = 0;
+        loop {
+            let (delta, rest) = Delta::between(previous, pixels, self.width, start).map_err(|e| format!("{:?}", e))?;
+            deltas.push(delta);
+            match rest {
+                Some(next) => start = next,
+                None => return Ok(deltas),
             }
+        }
+    }
 
-            let mut max = T::min_value();
-            while let Some(value) = seq.next_element()? {
-                if value > max {
-                    max = value;
-                }
+    // The commands that take the device from the last frame to this one, to
+    // be shown at `present_at` on the connection clock, or on the next Flush
+    pub fn next_frame(&mut self, pixels: &[Pixel], present_at: Option<u32>) -> Result<Vec<Command>, Box<dyn Error>> {
+        self.sequence = self.sequence.wrapping_add(1);
+        let keyframe = strips(pixels, self.width, self.encoding)?;
+
+        let deltas = match &self.previous {
+            Some(previous) if previous.len() == pixels.len() && self.since_keyframe < self.keyframe_interval => {
+                Some(self.deltas(previous, pixels)?)
+            }
+            _ => None,
+        };
+        self.previous = Some(pixels.to_vec());
+
+        // Send a keyframe instead when it is no bigger than the changes
+        let keyframe_len: usize = keyframe.iter().map(Frame::data_len).sum();
+        let commands = match deltas {
+            Some(deltas) if deltas.iter().map(|delta| delta.spans.len()).sum::<usize>() < keyframe_len => {
+                self.since_keyframe += 1;
+                let parts = deltas.len() as u16;
+                deltas
+                    .into_iter()
+                    .enumerate()
+                    .map(|(part, delta)| Command::Delta(self.header(part, parts, present_at), delta))
+                    .collect()
+            }
+            _ => {
+                self.since_keyframe = 0;
+                let parts = keyframe.len() as u16;
+                keyframe
+                    .into_iter()
+                    .enumerate()
+                    .map(|(part, frame)| Command::Keyframe(self.header(part, parts, present_at), frame))
+                    .collect()
             }
-            Ok(max)
+        };
+        Ok(commands)
+    }
+
+    fn header(&self, part: usize, parts: u16, present_at: Option<u32>) -> FrameHeader {
+        FrameHeader {
+            sequence: self.sequence,
+            part: part as u16,
+            parts,
+            present_at,
         }
     }
+}
 
-    let visitor = PixelVisitor(PhantomData);
-    deserialiser.deserialize_seq(visitor)
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keyframes_and_deltas() {
+        let mut stream = FrameStream::new(10, FrameEncoding::Auto, 2);
+        let mut pixels = vec![(0, 0, 0); 10 * 24];
+        let kinds = |commands: &[Command]| {
+            commands
+                .iter()
+                .map(|command| match command {
+                    Command::Keyframe(header, _) => ('k', header.sequence),
+                    Command::Delta(header, _) => ('d', header.sequence),
+                    _ => ('?', 0),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kinds(&stream.next_frame(&pixels, None).unwrap()), [('k', 1)]);
+        pixels[10] = (255, 0, 0);
+        assert_eq!(kinds(&stream.next_frame(&pixels, None).unwrap()), [('d', 2)]);
+        pixels[11] = (255, 0, 0);
+        assert_eq!(kinds(&stream.next_frame(&pixels, None).unwrap()), [('d', 3)]);
+        // Keyframe interval
+        assert_eq!(kinds(&stream.next_frame(&pixels, None).unwrap()), [('k', 4)]);
+
+        // Noise changes every pixel, so a raw keyframe beats the deltas
+        let noise: Vec<Pixel> = (0..10 * 24).map(|i| (i as u8, (i * 7) as u8, (i * 13) as u8)).collect();
+        assert_eq!(kinds(&stream.next_frame(&noise, None).unwrap()), [('k', 5)]);
+
+        // Bigger displays go in strips
+        let mut stream = FrameStream::new(50, FrameEncoding::Raw, 2);
+        assert_eq!(kinds(&stream.next_frame(&[(0, 0, 0); 50 * 24], None).unwrap()), [('k', 1); 3]);
+
+        // Every part carries the presentation time
+        let commands = stream.next_frame(&[(0, 0, 0); 50 * 24], Some(1234)).unwrap();
+        assert!(commands.iter().all(|command| matches!(command, Command::Delta(header, _) if header.present_at == Some(1234))));
+    }
+}